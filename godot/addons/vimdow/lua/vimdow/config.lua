@@ -4,6 +4,7 @@ return {
 	keybinds = {
 		-- toggle_breakpoint = "<leader>gb",
 		-- clear_breakpoints = "<leader>cb",
+		-- go_to = "<leader>gf",
 		-- release_focus ="<C-Esc>"
 	},
 
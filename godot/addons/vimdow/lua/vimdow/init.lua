@@ -2,72 +2,265 @@ _G.Vimdow = {
 	-- breakpoints managed by the godot editor
 	breakpoints = {},
 }
+-- this file is sourced on startup, so require("vimdow") gets the same table
+package.loaded.vimdow = Vimdow
 local env = os.getenv
 
-local BREAKPOINTS_GROUP = "vimdow_breakpoints"
+-- godot started neovim with --embed, so its channel is the rpc one on stdio
+for _, chan in ipairs(vim.api.nvim_list_chans()) do
+	if chan.mode == "rpc" and chan.stream == "stdio" then
+		vim.g.vimdow_channel = chan.id
+	end
+end
 
-function Vimdow.clear_breakpoints (buf)
-	if buf then
-		vim.fn.sign_unplace(BREAKPOINTS_GROUP, {
-			buffer = buf,
-		})
-		Vimdow.breakpoints[buf] = {}
-	else
-		vim.fn.sign_unplace(BREAKPOINTS_GROUP)
-		Vimdow.breakpoints = {}
+-- lets the rest of the plugin be loaded with require
+local plugin_dir = vim.fn.fnamemodify(debug.getinfo(1, "S").source:sub(2), ":p:h:h:h")
+vim.opt.runtimepath:append(plugin_dir)
+
+-- breakpoints are extmarks, so that they follow their lines as the buffer is edited
+local BREAKPOINTS_NS = vim.api.nvim_create_namespace "vimdow_breakpoints"
+
+-- sends a request to godot, printing the error it answers with
+local function request_godot (method, ...)
+	local result = vim.fn.rpcrequest(vim.g.vimdow_channel, method, ...)
+	if result ~= vim.NIL then
+		vim.print(result)
+	end
+end
+
+local function describe_breakpoint (bp)
+	local parts = {}
+	if bp.condition ~= "" then
+		table.insert(parts, "if " .. bp.condition)
+	end
+	if bp.hit_count > 0 then
+		table.insert(parts, "after " .. bp.hit_count .. " hits")
+	end
+	return table.concat(parts, ", ")
+end
+
+-- marks the line of a breakpoint, unless its buffer isn't loaded yet
+local function place_breakpoint_mark (path, line, bp)
+	local buf = vim.fn.bufnr(path)
+	if buf == -1 or not vim.api.nvim_buf_is_loaded(buf) then
+		bp.mark = nil
+		return
+	end
+
+	local desc = describe_breakpoint(bp)
+	bp.mark = vim.api.nvim_buf_set_extmark(buf, BREAKPOINTS_NS, math.min(line, vim.api.nvim_buf_line_count(buf)) - 1, 0, {
+		id = bp.mark,
+		sign_text = "",
+		sign_hl_group = Vimdow.set_breakpoint_hl,
+		virt_text = desc ~= "" and { { desc, "Comment" } } or nil,
+		priority = 43,
+	})
+end
+
+local function remove_breakpoint_mark (path, bp)
+	local buf = vim.fn.bufnr(path)
+	if bp.mark and buf ~= -1 then
+		vim.api.nvim_buf_del_extmark(buf, BREAKPOINTS_NS, bp.mark)
 	end
 end
 
-function Vimdow.set_breakpoint (buf, line, val, external)
-	if not Vimdow.breakpoints[buf] then
-		Vimdow.breakpoints[buf] = {}
+function Vimdow.clear_breakpoints (buf)
+	local paths = buf and { buf } or vim.tbl_keys(Vimdow.breakpoints)
+	for _, path in ipairs(paths) do
+		for _, bp in pairs(Vimdow.breakpoints[path] or {}) do
+			remove_breakpoint_mark(path, bp)
+		end
+		Vimdow.breakpoints[path] = nil
 	end
+end
+
+-- `opts` can set the `condition` and `hit_count` of the breakpoint
+function Vimdow.set_breakpoint (buf, line, val, external, opts)
+	local bps = Vimdow.breakpoints[buf] or {}
+	Vimdow.breakpoints[buf] = bps
 
+	local bp = bps[line]
 	if val then
-		if Vimdow.breakpoints[buf][line] then
+		if bp and not opts then
 			return
 		end
 
-		Vimdow.breakpoints[buf][line] = vim.fn.sign_place(0, BREAKPOINTS_GROUP, "GodotBreakpoint", buf, {
-			lnum = line,
-			priority = 43,
-		})
+		bp = bp or { condition = "", hit_count = 0 }
+		if opts then
+			bp.condition = opts.condition or ""
+			bp.hit_count = opts.hit_count or 0
+		end
+		bps[line] = bp
+		place_breakpoint_mark(buf, line, bp)
 	else
-		if not Vimdow.breakpoints[buf][line] then
+		if not bp then
 			return
 		end
 
-		vim.fn.sign_unplace(BREAKPOINTS_GROUP, {
-			buffer = buf,
-			id = Vimdow.breakpoints[buf][line],
-		})
-		Vimdow.breakpoints[buf][line] = nil
+		remove_breakpoint_mark(buf, bp)
+		bps[line] = nil
 	end
 
-	if not external then
-		local result = vim.fn.rpcrequest(1, "vimdow_set_breakpoint", buf, line, val)
-		if result ~= vim.NIL then
-			vim.print(result)
-		end
+	if external then
+		return
+	elseif val and opts then
+		request_godot("vimdow_set_breakpoint_options", buf, line, bp.condition, bp.hit_count)
+	else
+		request_godot("vimdow_set_breakpoint", buf, line, val)
 	end
 end
 
 function Vimdow.get_breakpoint (buf, line)
 	local bps = Vimdow.breakpoints[buf]
+	return bps ~= nil and bps[line] ~= nil
+end
+
+function Vimdow.toggle_breakpoint (buf, line)
+	Vimdow.set_breakpoint(buf, line, not Vimdow.get_breakpoint(buf, line))
+end
+
+-- marks the breakpoints that were set before their buffer was loaded
+local function place_pending_breakpoints (path)
+	for line, bp in pairs(Vimdow.breakpoints[path] or {}) do
+		if not bp.mark then
+			place_breakpoint_mark(path, line, bp)
+		end
+	end
+end
+
+-- moves the breakpoints of a buffer to the lines their extmarks were pushed to by an edit,
+-- and lets godot know. breakpoints whose lines were deleted into another one's are removed
+local function follow_breakpoint_marks (buf)
+	local path = vim.api.nvim_buf_get_name(buf)
+	local bps = Vimdow.breakpoints[path]
 	if not bps then
-		return false
-	elseif bps then
-		for k, _ in pairs(bps) do
-			if k == line then
-				return true
+		return
+	end
+
+	local rows = {}
+	for _, mark in ipairs(vim.api.nvim_buf_get_extmarks(buf, BREAKPOINTS_NS, 0, -1, {})) do
+		rows[mark[1]] = mark[2] + 1
+	end
+
+	local moved = {}
+	for line, bp in pairs(bps) do
+		local to = bp.mark and rows[bp.mark]
+		if to and to ~= line then
+			table.insert(moved, { line, to })
+		end
+	end
+	if #moved == 0 then
+		return
+	end
+
+	local moved_bps = {}
+	for _, move in ipairs(moved) do
+		moved_bps[move[1]] = bps[move[1]]
+		bps[move[1]] = nil
+	end
+
+	local moves = {}
+	for _, move in ipairs(moved) do
+		local from, to = move[1], move[2]
+		if bps[to] then
+			remove_breakpoint_mark(path, moved_bps[from])
+			request_godot("vimdow_set_breakpoint", path, from, false)
+		else
+			bps[to] = moved_bps[from]
+			table.insert(moves, move)
+		end
+	end
+	if #moves > 0 then
+		request_godot("vimdow_move_breakpoints", path, moves)
+	end
+end
+
+-- shows the breakpoints godot saved for the project, keyed by absolute path
+function Vimdow.load_breakpoints ()
+	local bps = vim.fn.rpcrequest(vim.g.vimdow_channel, "vimdow_get_breakpoints")
+	for path, list in pairs(bps) do
+		for _, bp in ipairs(list) do
+			Vimdow.set_breakpoint(path, bp.line, true, true, bp)
+		end
+	end
+end
+-- calls a method godot registered with its NeovimClient, like "play_current_scene".
+-- errors about the method or its arguments are raised
+function Vimdow.call (method, ...)
+	return vim.fn.rpcrequest(vim.g.vimdow_channel, method, ...)
+end
+
+-- the string or node path under the cursor, like the paths given to preload and load,
+-- `$NodePath`, `$"Node Path"`, `%UniqueName` or `get_node("NodePath")`
+local function goto_target ()
+	local line = vim.api.nvim_get_current_line()
+	local col = vim.api.nvim_win_get_cursor(0)[2] + 1
+
+	local start = 1
+	while true do
+		local s, e, _, text = line:find("([\"'])(.-)%1", start)
+		if not s or s > col then
+			break
+		elseif col <= e then
+			local before = line:sub(1, s - 1)
+			if before:match "[$%%]$" then
+				return before:sub(-1) .. text
+			elseif before:match "get_node%($" then
+				return "$" .. text
 			end
+			return text
+		end
+		start = e + 1
+	end
+
+	for s, path, e in line:gmatch "()([$%%][%w_/%%]+)()" do
+		if s <= col and col < e then
+			return path
 		end
-		return false
 	end
 end
 
-function Vimdow.toggle_breakpoint (buf, line)
-	Vimdow.set_breakpoint(buf, line, not Vimdow.get_breakpoint(buf, line))
+function Vimdow.go_to ()
+	local target = goto_target()
+	if not target then
+		vim.notify("Nothing to go to under the cursor", vim.log.levels.WARN)
+		return
+	end
+
+	local ok, result = pcall(vim.fn.rpcrequest, vim.g.vimdow_channel, "vimdow_goto", target, vim.api.nvim_buf_get_name(0))
+	if not ok then
+		vim.notify(tostring(result), vim.log.levels.ERROR)
+	elseif type(result) == "string" then
+		vim.cmd.edit(vim.fn.fnameescape(result))
+	end
+end
+
+-- (re)starts the client of godot's language server, with the port and project root in g:vimdow_lsp
+function Vimdow.start_lsp ()
+	local lsp = vim.g.vimdow_lsp
+	if not lsp then
+		return
+	end
+
+	-- exits of the clients being replaced aren't reported to godot
+	local generation = (Vimdow.lsp_generation or 0) + 1
+	Vimdow.lsp_generation = generation
+	vim.lsp.enable("gdscript", false)
+	for _, client in ipairs(vim.lsp.get_clients { name = "gdscript" }) do
+		client:stop()
+	end
+
+	vim.lsp.config("gdscript", {
+		cmd = vim.lsp.rpc.connect("127.0.0.1", lsp.port),
+		filetypes = { "gdscript" },
+		root_dir = lsp.root_dir,
+		on_exit = function ()
+			if Vimdow.lsp_generation == generation then
+				vim.rpcnotify(vim.g.vimdow_channel, "vimdow_lsp_exit")
+			end
+		end,
+	})
+	vim.lsp.enable "gdscript"
 end
 
 function Vimdow.setup (opts)
@@ -78,12 +271,8 @@ function Vimdow.setup (opts)
 	local v = Vimdow
 	v.root_dir = root_dir
 
-	-- Automatically connect to the language server
-	local langserver_port = env "GODOT_LANGSERVER_PORT"
-	vim.lsp.config("gdscript", {
-		cmd = vim.lsp.rpc.connect("127.0.0.1", tonumber(langserver_port)),
-	})
-	vim.lsp.enable "gdscript"
+	-- Automatically connect to the language server, godot sets g:vimdow_lsp once it's reachable
+	Vimdow.start_lsp()
 
 	local gd_version = opts.gd_version or env "GODOT_VERSION"
 
@@ -94,11 +283,6 @@ function Vimdow.setup (opts)
 	Vimdow.set_breakpoint_hl = "VimdowSetBreakpoint"
 	vim.api.nvim_set_hl(0, Vimdow.set_breakpoint_hl, { fg = colors.set_breakpoint or "#ff0016" })
 
-	vim.fn.sign_define("GodotBreakpoint", {
-		text = "",
-		texthl = Vimdow.set_breakpoint_hl,
-	})
-
 	vim.fn.sign_define("GodotBreakpointHover", {
 		text = "",
 		texthl = Vimdow.hover_breakpoint_hl,
@@ -113,7 +297,7 @@ function Vimdow.setup (opts)
 		end
 
 		local linenum = tonumber(o.fargs[1]) or vim.api.nvim_win_get_cursor(0)[1]
-		Vimdow.toggle_breakpoint(vim.fn.bufname(), linenum)
+		Vimdow.toggle_breakpoint(vim.api.nvim_buf_get_name(0), linenum)
 	end, {
 		nargs = "?",
 	})
@@ -121,16 +305,54 @@ function Vimdow.setup (opts)
 		desc = "toggle godot breakpoint on cursor line",
 	})
 
+	-- conditions and hit counts of the breakpoint on the cursor line, which is set if it wasn't
+	local function set_breakpoint_option (key, value)
+		local path = vim.api.nvim_buf_get_name(0)
+		local line = vim.api.nvim_win_get_cursor(0)[1]
+		local bp = (Vimdow.breakpoints[path] or {})[line] or {}
+		local opts = { condition = bp.condition, hit_count = bp.hit_count }
+		opts[key] = value
+		Vimdow.set_breakpoint(path, line, true, false, opts)
+	end
+	vim.api.nvim_create_user_command("VimdowBreakpointCondition", function (o)
+		set_breakpoint_option("condition", o.args)
+	end, {
+		nargs = "*",
+	})
+	vim.api.nvim_create_user_command("VimdowBreakpointHits", function (o)
+		set_breakpoint_option("hit_count", tonumber(o.args) or 0)
+	end, {
+		nargs = "?",
+	})
+
+	-- calling godot's methods from the command line, the arguments are converted to the types they take
+	vim.api.nvim_create_user_command("VimdowCall", function (o)
+		local ok, result = pcall(Vimdow.call, unpack(o.fargs))
+		if not ok then
+			vim.notify(tostring(result), vim.log.levels.ERROR)
+		elseif result ~= vim.NIL then
+			vim.print(result)
+		end
+	end, {
+		nargs = "+",
+	})
+
+	-- following resource and node paths
+	vim.api.nvim_create_user_command("VimdowGoto", Vimdow.go_to, {})
+	vim.keymap.set("n", keybinds.go_to or "<leader>gf", Vimdow.go_to, {
+		desc = "go to the godot resource or node under the cursor",
+	})
+
 	-- breakpoint clearing
 	local cb = keybinds.clear_breakpoints or "<leader>cb"
 	vim.api.nvim_create_user_command("VimdowClearBreakpoints", function (o)
 		if vim.bo.filetype ~= "gdscript" then
 			return
 		end
-		local path = o.fargs[1] or vim.fn.bufname()
+		local path = o.fargs[1] or vim.api.nvim_buf_get_name(0)
 		Vimdow.clear_breakpoints(path)
 
-		local result = vim.fn.rpcrequest(1, "vimdow_clear_breakpoints", path)
+		local result = vim.fn.rpcrequest(vim.g.vimdow_channel, "vimdow_clear_breakpoints", path)
 		if result ~= vim.NIL then
 			vim.print(result)
 		end
@@ -180,13 +402,73 @@ function Vimdow.setup (opts)
 			return
 		end
 
-		Vimdow.toggle_breakpoint(vim.fn.bufname(), pos.line)
+		Vimdow.toggle_breakpoint(vim.api.nvim_buf_get_name(0), pos.line)
 	end)
 
+	-- controlling a game paused by the godot debugger
+	for command, method in pairs {
+		VimdowStep = "vimdow_debug_step",
+		VimdowNext = "vimdow_debug_next",
+		VimdowContinue = "vimdow_debug_continue",
+		VimdowBreak = "vimdow_debug_break",
+	} do
+		vim.api.nvim_create_user_command(command, function ()
+			local result = vim.fn.rpcrequest(vim.g.vimdow_channel, method)
+			if result ~= vim.NIL then
+				vim.print(result)
+			end
+		end, {})
+	end
+
+	-- inspecting the variables of a paused game, in the frame selected in the quickfix list
+	vim.api.nvim_create_user_command("VimdowInspect", function (o)
+		local qf = vim.fn.getqflist { title = 0, idx = 0 }
+		local frame = qf.title == "Godot stack" and qf.idx - 1 or 0
+
+		local result
+		if o.args == "" then
+			result = vim.fn.rpcrequest(vim.g.vimdow_channel, "vimdow_inspect_frame", frame)
+		else
+			result = vim.fn.rpcrequest(vim.g.vimdow_channel, "vimdow_inspect_expression", o.args, frame)
+		end
+		if result ~= vim.NIL then
+			vim.print(result)
+		end
+	end, {
+		nargs = "*",
+	})
+
+	-- letting godot reload resources written by neovim
+	vim.api.nvim_create_autocmd("BufWritePost", {
+		group = vim.api.nvim_create_augroup("vimdow_resources", {}),
+		callback = function (ev)
+			vim.rpcnotify(vim.g.vimdow_channel, "vimdow_buf_write", vim.api.nvim_buf_get_name(ev.buf))
+		end,
+	})
+
+	-- keeping godot in sync with unsaved project buffers
+	vim.api.nvim_create_autocmd({ "BufReadPost", "BufNewFile" }, {
+		group = vim.api.nvim_create_augroup("vimdow_buffers", {}),
+		callback = function (ev)
+			local name = vim.api.nvim_buf_get_name(ev.buf)
+			if vim.startswith(name, root_dir) then
+				vim.rpcnotify(vim.g.vimdow_channel, "vimdow_buf_attach", ev.buf, name)
+				place_pending_breakpoints(name)
+			end
+		end,
+	})
+	vim.api.nvim_create_autocmd({ "TextChanged", "InsertLeave" }, {
+		group = vim.api.nvim_create_augroup("vimdow_breakpoint_lines", {}),
+		callback = function (ev)
+			follow_breakpoint_marks(ev.buf)
+		end,
+	})
+	Vimdow.load_breakpoints()
+
 	-- releasing focus from vimdow back to the editor
 	local rf = keybinds.release_focus or "<C-Esc>"
 	vim.keymap.set("n", rf, function ()
-		local result = vim.fn.rpcrequest(1, "release_focus")
+		local result = vim.fn.rpcrequest(vim.g.vimdow_channel, "release_focus")
 		if result ~= vim.NIL then
 			vim.print("Vimdow exited focus: " .. tostring(result))
 		end
@@ -196,3 +478,5 @@ end
 -- plugin initilization
 local user_config = dofile "addons/vimdow/lua/vimdow/config.lua"
 Vimdow.setup(user_config)
+
+return Vimdow
@@ -0,0 +1,85 @@
+-- Floating window showing the variables of a game paused by the godot debugger
+local M = {}
+
+-- flattens the expanded parts of the tree into lines,
+-- remembering which node each line belongs to
+local function render (nodes, depth, lines, line_nodes)
+	for _, node in ipairs(nodes) do
+		local marker = "  "
+		if #node.children > 0 then
+			marker = node.expanded and "▾ " or "▸ "
+		end
+
+		local line = string.rep("  ", depth) .. marker .. node.name
+		if node.type ~= "" then
+			line = line .. ": " .. node.type
+		end
+		table.insert(lines, line .. " = " .. node.value)
+		line_nodes[#lines] = node
+
+		if node.expanded then
+			render(node.children, depth + 1, lines, line_nodes)
+		end
+	end
+end
+
+function M.open (title, tree)
+	local buf = vim.api.nvim_create_buf(false, true)
+	vim.bo[buf].bufhidden = "wipe"
+	vim.bo[buf].filetype = "vimdow_inspect"
+
+	local line_nodes = {}
+	local function redraw ()
+		local lines = {}
+		line_nodes = {}
+		render(tree, 0, lines, line_nodes)
+		if #lines == 0 then
+			lines = { "(no variables)" }
+		end
+
+		vim.bo[buf].modifiable = true
+		vim.api.nvim_buf_set_lines(buf, 0, -1, false, lines)
+		vim.bo[buf].modifiable = false
+		return lines
+	end
+
+	local lines = redraw()
+	local width = 40
+	for _, line in ipairs(lines) do
+		width = math.max(width, vim.fn.strdisplaywidth(line))
+	end
+	width = math.min(width, math.floor(vim.o.columns * 0.8))
+	local height = math.min(math.max(#lines, 10), math.floor(vim.o.lines * 0.6))
+
+	local win = vim.api.nvim_open_win(buf, true, {
+		relative = "editor",
+		row = math.floor((vim.o.lines - height) / 2),
+		col = math.floor((vim.o.columns - width) / 2),
+		width = width,
+		height = height,
+		style = "minimal",
+		border = "rounded",
+		title = " " .. title .. " ",
+		title_pos = "center",
+	})
+
+	local function toggle ()
+		local node = line_nodes[vim.api.nvim_win_get_cursor(win)[1]]
+		if node and #node.children > 0 then
+			node.expanded = not node.expanded
+			redraw()
+		end
+	end
+
+	local function close ()
+		vim.api.nvim_win_close(win, true)
+	end
+
+	local opts = { buffer = buf, nowait = true }
+	vim.keymap.set("n", "<CR>", toggle, opts)
+	vim.keymap.set("n", "<Tab>", toggle, opts)
+	vim.keymap.set("n", "q", close, opts)
+	vim.keymap.set("n", "<Esc>", close, opts)
+end
+
+return M
@@ -9,12 +9,22 @@ var editor: VimdowEditor
 var window_wrapper: Window
 
 var debugger: VimdowDebugger
+var resolver := ResourceResolver.new()
+var error_log := VimdowErrorLog.new()
+
+## Waits for typing to settle before handing unsaved text to godot
+var _live_text_timer: Timer
+var _changed_buffers := {}
 
 var pop_out_shortcut: Shortcut
 var focus_shortcut: Shortcut
 
 const DEFAULT_SETTINGS = {
 	"path_to_nvim" : "/usr/bin/nvim",
+	# resources with these extensions are opened in vimdow
+	"handled_extensions" : PackedStringArray([
+		"gd", "gdshader", "gdshaderinc", "tres", "tscn", "cfg", "json",
+	]),
 }
 
 func _enter_tree() -> void:
@@ -62,6 +72,20 @@ func _enter_tree() -> void:
 	_make_visible(false)
 	
 	main_screen_changed.connect(_on_main_screen_changed)
+	editor.client.buffer_written.connect(_on_buffer_written)
+	editor.client.buffer_changed.connect(_on_buffer_changed)
+	# goes back to the errors of the saved file
+	editor.client.buffer_detached.connect(resolver.reload)
+	editor.client.register_request_handler("vimdow_goto", _vimdow_goto, [TYPE_STRING, TYPE_STRING])
+	editor.client.register_editor_methods()
+
+	_live_text_timer = Timer.new()
+	_live_text_timer.one_shot = true
+	_live_text_timer.wait_time = 0.5
+	_live_text_timer.timeout.connect(_apply_live_text)
+	add_child(_live_text_timer)
+
+	OS.add_logger(error_log)
 
 	debugger = VimdowDebugger.new()
 	# debugger.editor = editor
@@ -75,8 +99,18 @@ func _exit_tree() -> void:
 		editor.queue_free()
 	if window_wrapper:
 		window_wrapper.queue_free()
+	if _live_text_timer:
+		_live_text_timer.queue_free()
 	if debugger:
 		remove_debugger_plugin(debugger)
+	OS.remove_logger(error_log)
+
+func _process(_delta: float) -> void:
+	if not editor or not editor.attached:
+		return
+	var errors = error_log.take_changes()
+	if errors != null:
+		editor.client.set_diagnostics("editor", errors)
 
 func _input(event: InputEvent) -> void:
 	if event.is_pressed() and focus_shortcut.matches_event(event):
@@ -108,14 +142,89 @@ func _on_main_screen_changed(screen_name: String):
 	if screen_name != _get_plugin_name():
 		_last_main_screen = screen_name
 
+func _on_buffer_written(path: String):
+	var local_path := ProjectSettings.localize_path(path)
+	if not local_path.begins_with("res://"):
+		return
+	# lets the editor know the file changed, so it won't ask about it on refocus
+	EditorInterface.get_resource_filesystem().update_file(local_path)
+	# the errors that remain get reported again by the reload
+	error_log.clear_file(path)
+	resolver.reload(path)
+
+func _on_buffer_changed(path: String):
+	_changed_buffers[path] = true
+	_live_text_timer.start()
+
+func _apply_live_text():
+	for path in _changed_buffers:
+		var text := editor.client.get_buffer_text(path)
+		if resolver.needs_live_text(path, text):
+			error_log.clear_file(path)
+			resolver.apply_live_text(path, text)
+	_changed_buffers.clear()
+
+## Resolves what :VimdowGoto found under neovim's cursor in the file `from`. Scenes open
+## in godot, nodes get selected, and other files vimdow handles are sent back to neovim
+func _vimdow_goto(target: String, from: String) -> Array:
+	if target.begins_with("$") or target.begins_with("%"):
+		return _select_node(target, ProjectSettings.localize_path(from))
+
+	var path := _resolve_goto_path(target, from)
+	if path.is_empty():
+		return ["Can't find '%s'" % target, null]
+	# scenes are opened in godot even when vimdow edits their text
+	if path.get_extension() in ["tscn", "scn"]:
+		EditorInterface.open_scene_from_path(path)
+		var root := EditorInterface.get_edited_scene_root()
+		EditorInterface.set_main_screen_editor("3D" if root is Node3D else "2D")
+	elif resolver.handles_file(path):
+		return [null, ProjectSettings.globalize_path(path)]
+	else:
+		EditorInterface.edit_resource(load(path))
+	return [null, null]
+
+## The project path of a res:// or uid:// path, or one relative to the script `from`
+## like the ones given to preload. Empty if there's no such file
+func _resolve_goto_path(target: String, from: String) -> String:
+	var path := target
+	if path.begins_with("uid://"):
+		var id := ResourceUID.text_to_id(path)
+		path = ResourceUID.get_id_path(id) if ResourceUID.has_id(id) else ""
+	elif not path.begins_with("res://"):
+		path = ProjectSettings.localize_path(from).get_base_dir().path_join(path).simplify_path()
+	return path if FileAccess.file_exists(path) else ""
+
+## Selects the node a `$NodePath` or `%UniqueName` points to,
+## from the nodes of the edited scene that use the script at `script_path`
+func _select_node(target: String, script_path: String) -> Array:
+	var root := EditorInterface.get_edited_scene_root()
+	if not root:
+		return ["No scene is being edited", null]
+
+	var node_path := target.trim_prefix("$")
+	var nodes: Array[Node] = [root]
+	nodes.append_array(root.find_children("*", "", true, false))
+	for from_node in nodes:
+		var script: Script = from_node.get_script()
+		if not script or script.resource_path != script_path:
+			continue
+		var node := from_node.get_node_or_null(node_path)
+		if node:
+			EditorInterface.get_selection().clear()
+			EditorInterface.get_selection().add_node(node)
+			EditorInterface.edit_node(node)
+			return [null, null]
+	return ["'%s' isn't in the edited scene" % target, null]
+
 func _handles(object: Object) -> bool:
-	return object is Script
+	return resolver.handles(object)
 
 func _edit(object: Object):
 	if object == null:
 		return
 	
-	editor.open_file(ProjectSettings.globalize_path(object.resource_path))
+	editor.open_file(resolver.resolve(object))
 	editor.grab_focus()
 
 func _has_main_screen() -> bool:
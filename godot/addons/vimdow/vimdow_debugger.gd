@@ -4,82 +4,304 @@ extends EditorDebuggerPlugin
 
 var editor: VimdowEditor
 
-var breakpoints := {}
+## Breakpoints set from either editor, kept across restarts
+var breakpoints := BreakpointStore.new()
+
+## Times each breakpoint was reached since the game started, for hit counts
+var _hits := {}
+## Stack of a break that waits for its breakpoint's condition to be evaluated
+var _condition_frames: Array[Dictionary] = []
+## Set when neovim steps through the game, so that reaching a breakpoint's line doesn't check it
+var _stepping := false
+
+## Errors and warnings the running game reported, once per location and message
+var _runtime_errors: Array[Dictionary] = []
+
+
+## What neovim asked to inspect, and the variables that arrived for it so far
+var _inspect_title := ""
+var _inspect_vars: Array[Dictionary] = []
+var _inspect_remaining := 0
 
 func setup(e: VimdowEditor):
 	editor = e
-	editor.client.neovim_request.connect(_on_neovim_request)
-
-func _set_bp_data(buf: String, line: int, enabled: bool):
-	if breakpoints.has(buf):
-		breakpoints[buf][line] = enabled
-	else:
-		breakpoints[buf] = {
-			line : enabled
-		}
+	for handler in [
+		["vimdow_clear_breakpoints", vimdow_clear_breakpoints, [TYPE_STRING]],
+		["vimdow_set_breakpoint", vimdow_set_breakpoint, [TYPE_STRING, TYPE_INT, TYPE_BOOL]],
+		["vimdow_set_breakpoint_options", vimdow_set_breakpoint_options,
+				[TYPE_STRING, TYPE_INT, TYPE_STRING, TYPE_INT]],
+		["vimdow_move_breakpoints", vimdow_move_breakpoints, [TYPE_STRING, TYPE_ARRAY]],
+		["vimdow_get_breakpoints", vimdow_get_breakpoints, []],
+		["vimdow_debug_step", vimdow_debug_step, []],
+		["vimdow_debug_next", vimdow_debug_next, []],
+		["vimdow_debug_continue", vimdow_debug_continue, []],
+		["vimdow_debug_break", vimdow_debug_break, []],
+		["vimdow_inspect_frame", vimdow_inspect_frame, [TYPE_INT]],
+		["vimdow_inspect_expression", vimdow_inspect_expression, [TYPE_STRING, TYPE_INT]],
+	]:
+		editor.client.register_request_handler(handler[0], handler[1], handler[2])
+	breakpoints.load()
 
 func set_sesh_breakpoint(sesh: EditorDebuggerSession, buffer_name: String, line: int, enabled: bool):
 	sesh.set_breakpoint(ProjectSettings.localize_path(buffer_name), line, enabled)
 
+## Called when an error, warning or stack frame is picked in godot's debugger
 func _goto_script_line(script: Script, line: int) -> void:
 	var path := ProjectSettings.globalize_path(script.resource_path)
 	editor.open_file(path, line+1)
+	# godot switches to its own script editor
+	EditorInterface.set_main_screen_editor.call_deferred("Vimdow")
 
 func _breakpoints_cleared_in_tree() -> void:
 	for sesh in get_sessions():
-		for buf in breakpoints:
-			for line in breakpoints[buf]:
-				set_sesh_breakpoint(sesh, buf, line, false)
+		for path in breakpoints.get_paths():
+			for line in breakpoints.get_lines(path):
+				set_sesh_breakpoint(sesh, path, line, false)
 	editor.clear_breakpoints()
-	breakpoints.clear()
+	breakpoints.clear("")
 
 func _breakpoint_set_in_tree(script: Script, line: int, enabled: bool) -> void:
 	var path = ProjectSettings.globalize_path(script.resource_path)
 	for sesh in get_sessions():
 		sesh.set_breakpoint(script.resource_path, line, enabled)
-	_set_bp_data(path, line + 1, enabled)
+	breakpoints.set_breakpoint(path, line + 1, enabled)
 	editor.set_breakpoint(path, line + 1, enabled)
 
 func vimdow_clear_breakpoints(buf: String) -> Variant:
-	var bps = breakpoints.get(buf)
-	if not bps:
+	var lines := breakpoints.get_lines(buf)
+	if lines.is_empty():
 		return ["Buffer has no breakpoints", null]
-	for line in bps:
-		if bps.get(line):
-			vimdow_set_breakpoint(buf, line, false)
+	for line in lines:
+		vimdow_set_breakpoint(buf, line, false)
 	return [null, null]
 
 func vimdow_set_breakpoint(buf: String, line: int, enabled: bool) -> Variant:
-	_set_bp_data(buf, line, enabled)
+	breakpoints.set_breakpoint(buf, line, enabled)
 	for sesh in get_sessions():
 		set_sesh_breakpoint(sesh, buf, line, enabled)
 	return [null, null]
 
+func vimdow_set_breakpoint_options(buf: String, line: int, condition: String, hit_count: int) -> Variant:
+	if not breakpoints.has_breakpoint(buf, line):
+		vimdow_set_breakpoint(buf, line, true)
+	breakpoints.set_options(buf, line, condition, hit_count)
+	return [null, null]
+
+## Follows breakpoints whose lines were moved by edits in neovim, `moves` are [from, to] pairs
+func vimdow_move_breakpoints(buf: String, moves: Array) -> Variant:
+	for sesh in get_sessions():
+		for move in moves:
+			set_sesh_breakpoint(sesh, buf, move[0], false)
+		for move in moves:
+			set_sesh_breakpoint(sesh, buf, move[1], true)
+	breakpoints.move_breakpoints(buf, moves)
+	return [null, null]
+
+func vimdow_get_breakpoints() -> Variant:
+	return [null, breakpoints.get_global_breakpoints()]
+
 func _setup_session(session_id: int) -> void:
 	var sesh = get_session(session_id)
 	sesh.started.connect(_on_session_started.bind(session_id))
 	sesh.stopped.connect(_on_session_stopped)
-	sesh.breaked.connect(_on_session_break)
+	sesh.continued.connect(_on_session_continued)
+	# breaks are shown from the stack dump the game sends after breaking, see _on_debug_data
+	_connect_debug_data(session_id)
+
+## A session only tells whether its game runs or is paused. The stack, variables and errors
+## of the game come from the session's tab in the debugger dock. Godot gives the sessions
+## the index of their tab as id. Returns false if there's no such tab
+func _connect_debug_data(session_id: int) -> bool:
+	var tab := _debugger_tab(session_id)
+	if not tab:
+		return false
+	var on_debug_data := _on_debug_data.bind(session_id)
+	if not tab.is_connected("debug_data", on_debug_data):
+		tab.connect("debug_data", on_debug_data)
+	return true
+
+## The ScriptEditorDebugger of a session, which is the control of the session's tab
+func _debugger_tab(session_id: int) -> Node:
+	for tab in EditorInterface.get_base_control()\
+			.find_children("*", "ScriptEditorDebugger", true, false):
+		var tabs := tab.get_parent() as TabContainer
+		if tabs and tabs.get_tab_idx_from_control(tab) == session_id:
+			return tab
+	return null
+
+## Finds a session that is running, and is (or isn't) paused
+func _debug_session(paused: bool) -> EditorDebuggerSession:
+	for sesh in get_sessions():
+		if sesh.is_active() and sesh.is_breaked() == paused:
+			return sesh
+	return null
+
+## Sends a message to the game of `session_id`, or to any game when it's -1
+func _send_debug_message(message: String, paused: bool, session_id := -1) -> Variant:
+	var sesh := _debug_session(paused) if session_id < 0 else get_session(session_id)
+	if not sesh:
+		return ["No %s game to debug" % ("paused" if paused else "running"), null]
+	sesh.send_message(message, [])
+	return [null, null]
+
+func vimdow_debug_step() -> Variant:
+	_stepping = true
+	return _send_debug_message("step", true)
+
+func vimdow_debug_next() -> Variant:
+	_stepping = true
+	return _send_debug_message("next", true)
+
+func vimdow_debug_continue() -> Variant:
+	return _send_debug_message("continue", true)
+
+func vimdow_debug_break() -> Variant:
+	return _send_debug_message("break", false)
+
+func _start_inspect(title: String, message: String, data: Array) -> Variant:
+	var sesh := _debug_session(true)
+	if not sesh:
+		return ["No paused game to inspect", null]
+	_inspect_title = title
+	_inspect_vars.clear()
+	sesh.send_message(message, data)
+	return [null, null]
+
+func _finish_inspect():
+	editor.client.show_variables(_inspect_title, _inspect_vars)
+	_inspect_title = ""
+	_inspect_vars.clear()
+
+func vimdow_inspect_frame(frame: int) -> Variant:
+	return _start_inspect("Frame %d" % frame, "get_stack_frame_vars", [frame])
+
+func vimdow_inspect_expression(expression: String, frame: int) -> Variant:
+	return _start_inspect(expression, "evaluate", [expression, frame])
+
+## Godot's debugger breaks on every breakpoint, so their conditions and hit counts are
+## checked here. Returns false if the game was continued, or waits on a condition
+func _check_breakpoint(frames: Array[Dictionary], session_id: int) -> bool:
+	var top := frames[0]
+	var options := breakpoints.get_options(top.file, top.line)
+	if options.is_empty():
+		return true
+
+	var key := "%s:%d" % [top.file, top.line]
+	_hits[key] = _hits.get(key, 0) + 1
+	if _hits[key] < options.hit_count:
+		_send_debug_message("continue", true, session_id)
+		return false
+
+	if options.condition.is_empty():
+		return true
+	_condition_frames = frames
+	get_session(session_id).send_message("evaluate", [options.condition, 0])
+	return false
+
+func _show_break(frames: Array[Dictionary]):
+	editor.client.show_break(frames)
+	# godot switches to its own script editor when a game breaks
+	EditorInterface.set_main_screen_editor.call_deferred("Vimdow")
+
+static func _parse_stack_dump(data: Array) -> Array[Dictionary]:
+	var frames: Array[Dictionary] = []
+	# the frames are flattened into [file, line, function] triples,
+	# which can be preceded by the total amount of elements
+	var start := data.size() % 3
+	for i in range(start, data.size(), 3):
+		frames.append({
+			file = data[i],
+			line = data[i + 1],
+			function = data[i + 2],
+		})
+	return frames
+
+## Turns an "error" message into a diagnostic, pointing at the script that caused it
+## rather than the engine's code. The message is laid out as
+## [hour, minute, second, msec, file, function, line, error, description, warning,
+## then the call stack's size and its [file, function, line] triples]
+static func _parse_error(data: Array) -> Dictionary:
+	var file: String = data[4]
+	var line: int = data[6]
+	if data.size() > 13 and data[10] > 0:
+		file = data[11]
+		line = data[13]
+	if not file.begins_with("res://"):
+		return {}
+	return {
+		file = file,
+		line = line,
+		message = data[8] if data[8] else data[7],
+		severity = "warning" if data[9] else "error",
+	}
+
+func _on_debug_data(msg: String, data: Array, session_id: int):
+	match msg:
+		"stack_dump":
+			var frames := _parse_stack_dump(data)
+			if frames.is_empty():
+				return
+			var stepped := _stepping
+			_stepping = false
+			if stepped or _check_breakpoint(frames, session_id):
+				_show_break(frames)
+		"error":
+			var error := _parse_error(data)
+			if error.is_empty() or _runtime_errors.has(error):
+				return
+			_runtime_errors.append(error)
+			editor.client.set_diagnostics("runtime", _runtime_errors)
+		"debug_exit":
+			_condition_frames = []
+			_stepping = false
+			editor.client.clear_break()
+		# a variable is sent as [name, scope, type, value]
+		"stack_frame_vars":
+			if _inspect_title.is_empty():
+				return
+			_inspect_remaining = data[0]
+			if _inspect_remaining == 0:
+				_finish_inspect()
+		"stack_frame_var":
+			if _inspect_title.is_empty():
+				return
+			_inspect_vars.append({ name = data[0], scope = data[1], value = data[3] })
+			_inspect_remaining -= 1
+			if _inspect_remaining <= 0:
+				_finish_inspect()
+		"evaluation_return":
+			if not _condition_frames.is_empty():
+				var frames := _condition_frames
+				_condition_frames = []
+				if data[3]:
+					_show_break(frames)
+				else:
+					_send_debug_message("continue", true, session_id)
+				return
+			if _inspect_title.is_empty():
+				return
+			_inspect_vars.append({ name = data[0], value = data[3] })
+			_finish_inspect()
 
 func _on_session_started(session_id: int):
 	var sesh = get_session(session_id)
-	for path in breakpoints:
-		for line in breakpoints[path]:
-			if breakpoints[path][line]:
-				set_sesh_breakpoint(sesh, path, line, true)
+	# the tab may have been added after the session
+	if not _connect_debug_data(session_id):
+		var error := "Vimdow: no debugger tab for session %d, its breaks won't show in neovim" % session_id
+		push_error(error)
+		editor.notify_error(error)
+	_hits.clear()
+	_runtime_errors.clear()
+	editor.client.set_diagnostics("runtime", _runtime_errors)
+	for path in breakpoints.get_paths():
+		for line in breakpoints.get_lines(path):
+			set_sesh_breakpoint(sesh, path, line, true)
+	# neovim shows what the game breaks on
+	editor.sync_breakpoints(breakpoints.get_global_breakpoints())
 
 
 func _on_session_stopped():
-	pass
-
-func _on_session_break(can_debug: bool):
-	pass
-
-func _on_neovim_request(msgid: int, method: String, params: Array) -> void:
-	# neovim surrounds this with quotes for some reason
-	method = method.lstrip('"').rstrip('"')
-	if has_method(method):
-		var err_and_result = callv(method, params)
-		assert(err_and_result.size() == 2,
-			"rpc responses need a 2 element array of [error, result]")
-		editor.client.respond(msgid, err_and_result[0], err_and_result[1])
+	editor.client.clear_break()
+
+func _on_session_continued():
+	editor.client.clear_break()
@@ -7,12 +7,9 @@ extends MarginContainer
 # NOTE: an option in the future might be to have an "ext_multigrid" toggle that 
 # will split the windows into their own separate windows. So these variables are unchanged for now
 var grid_index: int = 1
-var grid_width: int
-var grid_height: int
 var mode: String
 var mode_idx: int
 var mode_info: Array
-var hl := {}
 var hl_groups := {}
 var options := {}
 var cwd: String
@@ -25,7 +22,6 @@ var cwd: String
 var viewport_lock: Window
 var attached := false
 
-var _row_wraps: Array
 var _redraw_batch := []
 var _inputs_buffer: Array[InputEventKey] = []
 var _mouse_buffer: Array[InputEvent] = []
@@ -35,6 +31,8 @@ var _option_set
 ## Configuration Handling ##
 const MAIN_SECTION = "neovim"
 const THEME_SECTION = "theme"
+const LSP_SECTION = "language_server"
+const LANGSERVER_PORT_SETTING = "network/language_server/remote_port"
 var _conf_path: String
 var _conf: ConfigFile
 
@@ -42,6 +40,7 @@ var _conf: ConfigFile
 #region SHORTCUTS
 var increase_fontsize_shortcut: Shortcut
 var decrease_fontsize_shortcut: Shortcut
+var paste_shortcut: Shortcut
 #endregion
 
 func _init() -> void:
@@ -58,8 +57,16 @@ func _init() -> void:
 	dfev.keycode = KEY_MINUS
 	decrease_fontsize_shortcut.events = [dfev]
 
+	paste_shortcut = Shortcut.new()
+	var pev = InputEventKey.new()
+	pev.ctrl_pressed = true
+	pev.shift_pressed = true
+	pev.keycode = KEY_V
+	paste_shortcut.events = [pev]
+
 
 func _ready() -> void:
+	client.register_request_handler("release_focus", _release_focus_request, [])
 	_conf = ConfigFile.new()
 	if _is_standalone():
 		_conf_path = (
@@ -76,13 +83,24 @@ func _ready() -> void:
 		var es = ei.get_editor_settings()
 		es.add_shortcut("vimdow/increase_font_size", increase_fontsize_shortcut)
 		es.add_shortcut("vimdow/decrease_font_size", decrease_fontsize_shortcut)
+		es.add_shortcut("vimdow/paste", paste_shortcut)
 		$ColorRect.color = es.get_setting("interface/theme/base_color")
 
+func _enter_tree() -> void:
+	# the editor can be moved between windows, see lock_to_window
+	get_window().files_dropped.connect(_on_window_files_dropped)
+	if not _is_standalone():
+		_get_editor_interface().get_editor_settings()\
+				.settings_changed.connect(_on_editor_settings_changed)
+
 func _exit_tree() -> void:
+	get_window().files_dropped.disconnect(_on_window_files_dropped)
 	if not _is_standalone():
 		var es = _get_editor_interface().get_editor_settings()
 		es.remove_shortcut("vimdow/increase_font_size")
 		es.remove_shortcut("vimdow/decrease_font_size")
+		es.remove_shortcut("vimdow/paste")
+		es.settings_changed.disconnect(_on_editor_settings_changed)
 
 func start() -> void:
 	if ProjectSettings.get_setting("vimdow/debug/log_msgpack"):
@@ -118,10 +136,6 @@ func start() -> void:
 
 		lock_to_window(r)
 	else:
-		OS.set_environment("GODOT_LANGSERVER_PORT", str(_get_editor_interface()\
-			.get_editor_settings()\
-			.get_setting("network/language_server/remote_port")))
-
 		OS.set_environment("GODOT_VERSION", Engine.get_version_info().string)
 	
 	var args := PackedStringArray(["--embed"])
@@ -132,16 +146,25 @@ func start() -> void:
 		])
 	args.append_array(OS.get_cmdline_user_args())
 
-	client.spawn(_conf.get_value(MAIN_SECTION, "path_to_nvim"), args)
+	# sessions are recorded and replayed for bug reports, see the README
+	var nvim_path = _conf.get_value(MAIN_SECTION, "path_to_nvim")
+	if OS.has_environment("VIMDOW_REPLAY"):
+		client.replay(OS.get_environment("VIMDOW_REPLAY"))
+	elif OS.has_environment("VIMDOW_RECORD"):
+		client.spawn_recorded(nvim_path, args, OS.get_environment("VIMDOW_RECORD"))
+	else:
+		client.spawn(nvim_path, args)
 	await get_tree().create_timer(.1).timeout
 	assert(client.is_running())
 	var initial_size := get_editor_grid_size(w.size)
 	# attached = client.attach(initial_size.x, initial_size.y)
-	client.request("nvim_ui_attach", [initial_size.x, initial_size.y, {
+	client.nvim_ui_attach(initial_size.x, initial_size.y, {
 		"ext_linegrid" : true,
 		"rgb" : true,
-	}])
+	})
 	attached = true # may come up with a better way to assert this
+	client.register_clipboard_provider()
+	_connect_language_server()
 	
 	var file = ProjectSettings.get_setting("vimdow/edit_file")
 	if file:
@@ -166,6 +189,8 @@ func _gui_input(event: InputEvent) -> void:
 		elif decrease_fontsize_shortcut.matches_event(event):
 			theme.set_font_size("font_size", "VimdowEditor", theme.get_font_size("font_size", "VimdowEditor") - 1)
 			try_resize()
+		elif paste_shortcut.matches_event(event):
+			client.paste(DisplayServer.clipboard_get())
 		else:
 			_inputs_buffer.append(event)
 	elif _acceptable_mouse(event):
@@ -225,14 +250,31 @@ func _on_neovim_client_neovim_event(method: String, params: Array) -> void:
 				_redraw_batch.push_back(event)
 
 
-func _on_neovim_client_neovim_request(msgid: int, method: String, _params: Array) -> void:
-	if method.lstrip('"').rstrip('"') == "release_focus":
-		release_focus()
-		client.respond(msgid, null, null)
+func _on_window_files_dropped(files: PackedStringArray) -> void:
+	$VimdowWindow.drop_os_files(files)
+
+
+## Neovim gives the focus back to the editor with its release_focus mapping
+func _release_focus_request() -> Array:
+	release_focus()
+	return [null, null]
 
-func _grid_assert(grid: int):
-	assert(grid == grid_index, "Shouldn't receive an index for a different grid")
 
+## Lets the client connect neovim to the editor's language server, once it's listening.
+## The standalone app has no editor, it connects to the one in its config file if any
+func _connect_language_server():
+	if _is_standalone():
+		client.set_language_server(_conf.get_value(LSP_SECTION, "port", 0),
+				_conf.get_value(LSP_SECTION, "root_dir", ""))
+		return
+	var port: int = _get_editor_interface().get_editor_settings()\
+			.get_setting(LANGSERVER_PORT_SETTING)
+	client.set_language_server(port, ProjectSettings.globalize_path("res://"))
+
+func _on_editor_settings_changed():
+	var es = _get_editor_interface().get_editor_settings()
+	if attached and es.check_changed_settings_in_group(LANGSERVER_PORT_SETTING):
+		_connect_language_server()
 
 #region NEOVIM_COMMANDS
 
@@ -241,28 +283,52 @@ func try_resize() -> void:
 	if not is_node_ready() or not attached:
 		return
 	var s := get_editor_grid_size(w.size)
-	client.request("nvim_ui_try_resize", [s.x, s.y])
+	client.nvim_ui_try_resize(s.x, s.y)
 
-## Opens a file in vimdow
+## Opens a file in vimdow, on `line` if it's positive
 func open_file(path: String, line: int = -1):
-	if attached:
-		var cmd = ("e +%d " % line if line > 0 else "e ") + path
-		client.request("nvim_command", [cmd])
+	if not attached:
+		return
+	# nvim_cmd takes the path as it is, :edit would need it escaped
+	var batch := client.batch().add("nvim_cmd", [{ cmd = "edit", args = [path] }, {}])
+	if line > 0:
+		batch.add("nvim_cmd", [{ cmd = "normal", bang = true, args = ["%dG" % line] }, {}])
+	batch.send()
+
+## Shows an error in neovim, for failures that would go unnoticed in godot's output
+func notify_error(message: String):
+	if not attached:
+		return
+	client.nvim_exec_lua("vim.notify(..., vim.log.levels.ERROR)", [message])
 
 ## Instructs the lua plugin to clear all breakpoints. Can optionally specify the buffer to clear
 func clear_breakpoints(path = ""):
 	assert(attached)
-	client.request("nvim_command", [ "VimdowClearBreakpoints " + path ])
+	client.nvim_exec_lua("Vimdow.clear_breakpoints(...)", [path] if path else [])
 
 ## Instructs the lua plugin to set the value of a breakpoint
 func set_breakpoint(path: String, line: int, enabled: bool):
 	assert(attached)
-	var command_str = "lua Vimdow.set_breakpoint(\"%s\", %d, %s, true)" % [path, line, enabled]
-	client.request("nvim_command", [ command_str ])
+	client.nvim_exec_lua("Vimdow.set_breakpoint(...)", [path, line, enabled, true])
+
+## Shows `breakpoints` in neovim in place of the ones it has, in one round trip.
+## They're keyed by absolute path, like `BreakpointStore.get_global_breakpoints()` returns them
+func sync_breakpoints(breakpoints: Dictionary):
+	if not attached:
+		return
+	var batch := client.batch().add("nvim_exec_lua", ["Vimdow.clear_breakpoints()", []])
+	for path in breakpoints:
+		for bp in breakpoints[path]:
+			batch.add("nvim_exec_lua", ["Vimdow.set_breakpoint(...)", [path, bp.line, true, true, bp]])
+	batch.send()
 
 #endregion
 
 #region REDRAW_EVENTS
+## Drawn by the client, which keeps the grids and highlights. See NeovimClient.ui
+const GRID_EVENTS := ["grid_resize", "grid_clear", "grid_destroy", "grid_cursor_goto",
+		"grid_line", "grid_scroll", "hl_attr_define"]
+
 func flush():
 	var  i := 0
 	var dbg = ProjectSettings.get_setting("vimdow/debug/log_msgpack")
@@ -272,7 +338,7 @@ func flush():
 		if has_method(event_name):
 			for e in event:
 				callv(event_name, e)
-		elif dbg:
+		elif dbg and not event_name in GRID_EVENTS:
 			_redraw_events.store_line("[%d] %s: %s" %[
 				i,
 				event_name, 
@@ -283,8 +349,7 @@ func flush():
 		_redraw_events.flush()
 		_log_options()
 	
-	assert(not hl.is_empty())
-	w.flush(hl, mode_info[mode_idx])
+	w.flush(client, mode_info[mode_idx])
 
 
 static func rgb_to_color(rgb: int) -> Color:
@@ -296,26 +361,8 @@ static func rgb_to_color(rgb: int) -> Color:
 	)
 
 
-func _add_hl(hl_id:int, attr: Dictionary):
-	for color_attr in ["foreground", "background", "special"]:
-		if attr.has(color_attr):
-			attr[color_attr] = rgb_to_color(attr[color_attr])
-	
-	hl[hl_id] = attr
-
-
-func default_colors_set(rgb_fg: int, rgb_bg: int, rgb_sp: int, _cterm_fg, _cterm_bg):
-	_add_hl(0, {
-		foreground = rgb_fg,
-		background = rgb_bg,
-		special = rgb_sp
-	})
-	
-	$ColorRect.color = hl[0].background
-
-func hl_attr_define(id: int, rgb_attr: Dictionary, 
-	_cterm_attr: Dictionary, _info: Array):
-	_add_hl(id, rgb_attr)
+func default_colors_set(_rgb_fg: int, rgb_bg: int, _rgb_sp: int, _cterm_fg, _cterm_bg):
+	$ColorRect.color = rgb_to_color(rgb_bg)
 
 
 func hl_group_set(group_name: String, hl_id: int):
@@ -348,16 +395,6 @@ func chdir(dir: String):
 	cwd = dir
 
 
-func grid_resize(grid: int, width: int, height: int):
-	_grid_assert(grid)
-	grid_width = width
-	grid_height = height
-	_row_wraps = []
-	for _i in height:
-		_row_wraps.append(false)
-	w.set_grid_size(width, height)
-
-
 # this shouldn't be sent if ext_multigrid == false.
 # might be a bug but have this to just get it out of logs 
 func win_viewport(_grid: int, _win: int, _topline: int, _botline: int, 
@@ -365,77 +402,11 @@ func win_viewport(_grid: int, _win: int, _topline: int, _botline: int,
 	return
 
 
-func grid_line(grid: int, row: int, col_start: int, cells: Array, wrapline: bool):
-	_grid_assert(grid)
-	_row_wraps[row] = wrapline
-	var old_line = w.get_line(row)
-	var line = old_line.substr(0, col_start)
-	var last_hl_id = null
-	var col_end = col_start
-	var regions = $VimdowWindow/Highlighter.hl_regions[row]
-	for cell in cells:
-		var col = col_end
-		match cell:
-			[var text, var hl_id, var repeat]:
-				line += text.repeat(repeat)
-				col_end += repeat
-				last_hl_id = hl_id
-				for i in repeat:
-					regions[col + i] = last_hl_id
-			[var text, var hl_id]:
-				line += text
-				col_end += 1
-				last_hl_id = hl_id
-				regions[col] = last_hl_id
-			[var text]:
-				line += text
-				col_end += 1
-				regions[col] = last_hl_id
-		assert(hl.has(last_hl_id))
-	
-	line += old_line.substr(line.length())
-	w.set_line(row, line)
-
-
-func grid_clear(grid: int):
-	_grid_assert(grid)
-	w.clear()
-
-
-func grid_cursor_goto(grid: int, row: int, col: int):
-	_grid_assert(grid)
-	w.cursor.x = col
-	w.cursor.y = row
-
-
-func grid_scroll(grid: int, top: int, bot: int, 
-	left: int, right: int, rows: int, _cols: int):
-	_grid_assert(grid)
-	
-	var lines := []
-	var hl_regions := []
-	for i in range(top, bot):
-		lines.append(w.get_line(i))
-		hl_regions.append($VimdowWindow/Highlighter.hl_regions[i].duplicate())
-	
-	var dst_top := top - rows
-	var dst_bot := bot - rows
-	
-	for row in range(dst_top, dst_bot):
-		var src_line = lines.pop_front()
-		var src_regions = hl_regions.pop_front()
-		if row < top or row >= bot:
-			continue
-		var dst_line = w.get_line(row)
-		var line = dst_line.substr(0, left) + src_line.substr(left, right - left) + dst_line.substr(right)
-		w.set_line(row, line)
-		for i in range(left, right):
-			$VimdowWindow/Highlighter.hl_regions[row][i] = src_regions[i]
-
-
 #region OPTION_SET
 func option_set(opt_name: String, value: Variant):
 	options[opt_name] = value
+	if opt_name == "mousemoveevent":
+		client.set_mouse_move_events(value)
 #endregion OPTION_SET
 
 #endregion REDRAW_EVENTS
@@ -0,0 +1,63 @@
+@tool
+class_name VimdowErrorLog
+extends Logger
+## Collects the errors and warnings godot reports about project files while editing,
+## like the parse errors of scripts. Loggers are called from any thread, so the
+## errors are kept until the main thread takes them.
+
+## Older errors are dropped past this amount
+const MAX_ERRORS = 200
+
+## vimdow's own errors aren't the project's, they stay in godot's output
+const OWN_FILES = "res://addons/vimdow/"
+
+var _mutex := Mutex.new()
+var _errors: Array[Dictionary] = []
+var _changed := false
+
+func _log_error(_function: String, file: String, line: int, code: String, rationale: String,
+		_editor_notify: bool, error_type: int, script_backtraces: Array[ScriptBacktrace]) -> void:
+	# errors raised by the engine's code point at the script that caused them
+	var frame := _project_frame(script_backtraces)
+	if not frame.is_empty():
+		file = frame.file
+		line = frame.line
+	if not file.begins_with("res://") or file.begins_with(OWN_FILES):
+		return
+
+	_mutex.lock()
+	_errors.append({
+		file = file,
+		line = line,
+		message = rationale if rationale else code,
+		severity = "warning" if error_type == ERROR_TYPE_WARNING else "error",
+	})
+	if _errors.size() > MAX_ERRORS:
+		_errors.pop_front()
+	_changed = true
+	_mutex.unlock()
+
+## The innermost frame of the backtraces that isn't in vimdow's scripts, as { file, line }
+static func _project_frame(script_backtraces: Array[ScriptBacktrace]) -> Dictionary:
+	for backtrace in script_backtraces:
+		for i in backtrace.get_frame_count():
+			if not backtrace.get_frame_file(i).begins_with(OWN_FILES):
+				return { file = backtrace.get_frame_file(i), line = backtrace.get_frame_line(i) }
+	return {}
+
+## Forgets the errors of a file, before it's reloaded and reports them again
+func clear_file(path: String) -> void:
+	path = ProjectSettings.localize_path(path)
+	_mutex.lock()
+	var count := _errors.size()
+	_errors.assign(_errors.filter(func(e): return e.file != path))
+	_changed = _changed or _errors.size() != count
+	_mutex.unlock()
+
+## Returns the errors if they changed since they were last taken, or null
+func take_changes() -> Variant:
+	_mutex.lock()
+	var errors = _errors.duplicate() if _changed else null
+	_changed = false
+	_mutex.unlock()
+	return errors
@@ -0,0 +1,12 @@
+extends SceneTree
+## Runs vimdow's in-engine tests, which need a build with `cargo build --features itest`:
+## godot --headless --path godot -s res://tests/run_itests.gd
+
+
+func _initialize() -> void:
+	if not ClassDB.class_exists("VimdowTests"):
+		push_error("VimdowTests isn't registered, build vimdow with --features itest")
+		quit(1)
+		return
+	var tests: RefCounted = ClassDB.instantiate("VimdowTests")
+	quit(0 if tests.run() == 0 else 1)
@@ -0,0 +1,55 @@
+-- Writes the API metadata of this neovim to api-info.msgpack, keeping the functions vimdow
+-- binds. Run from rust/api after upgrading neovim, then give CI the same neovim version:
+--   nvim -l update.lua
+local bound = {
+	"nvim_buf_attach",
+	"nvim_buf_call",
+	"nvim_buf_detach",
+	"nvim_buf_get_lines",
+	"nvim_buf_get_name",
+	"nvim_buf_is_valid",
+	"nvim_buf_line_count",
+	"nvim_buf_set_lines",
+	"nvim_buf_set_name",
+	"nvim_call_atomic",
+	"nvim_call_function",
+	"nvim_cmd",
+	"nvim_command",
+	"nvim_exec",
+	"nvim_exec2",
+	"nvim_exec_lua",
+	"nvim_get_api_info",
+	"nvim_get_current_buf",
+	"nvim_get_current_line",
+	"nvim_get_current_tabpage",
+	"nvim_get_current_win",
+	"nvim_input",
+	"nvim_input_mouse",
+	"nvim_list_bufs",
+	"nvim_paste",
+	"nvim_set_var",
+	"nvim_tabpage_get_win",
+	"nvim_tabpage_is_valid",
+	"nvim_tabpage_list_wins",
+	"nvim_ui_attach",
+	"nvim_ui_detach",
+	"nvim_ui_try_resize",
+	"nvim_win_get_buf",
+	"nvim_win_get_cursor",
+	"nvim_win_get_tabpage",
+	"nvim_win_is_valid",
+	"nvim_win_set_buf",
+	"nvim_win_set_cursor",
+}
+
+local info = vim.fn.api_info()
+info.functions = vim.tbl_filter(function(f)
+	return vim.list_contains(bound, f.name)
+end, info.functions)
+-- the generator only reads these
+info.ui_events = nil
+info.ui_options = nil
+
+local file = assert(io.open("api-info.msgpack", "wb"))
+file:write(vim.mpack.encode(info))
+file:close()
@@ -0,0 +1,232 @@
+//! Generates the bindings of neovim's API in `src/neovim/api.rs` from `api/api-info.msgpack`
+
+use std::env;
+use std::fmt::Write;
+use std::fs::{self, File};
+use std::path::Path;
+
+use vimdow_core::api_info::{ApiFunction, ApiInfo, ApiParam, ApiType};
+
+const API_INFO: &str = "api/api-info.msgpack";
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self",
+    "static", "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized",
+    "use", "virtual", "where", "while", "yield",
+];
+
+fn ident(p: &ApiParam) -> String {
+    if KEYWORDS.contains(&p.name.as_str()) {
+        format!("{}_", p.name)
+    } else {
+        p.name.clone()
+    }
+}
+
+// the type a parameter has in the rust methods
+fn rust_type(ty: &ApiType) -> String {
+    match ty {
+        ApiType::Boolean => "bool".into(),
+        ApiType::Integer => "i64".into(),
+        ApiType::Float => "f64".into(),
+        ApiType::String => "&str".into(),
+        ApiType::Array => "Vec<Value>".into(),
+        ApiType::Dict => "Vec<(Value, Value)>".into(),
+        ApiType::Ext(name) => format!("&{name}"),
+        ApiType::Void | ApiType::Object | ApiType::LuaRef => "Value".into(),
+    }
+}
+
+// the type a parameter has in the #[func]s
+fn godot_type(ty: &ApiType) -> String {
+    match ty {
+        ApiType::Boolean => "bool".into(),
+        ApiType::Integer => "i64".into(),
+        ApiType::Float => "f64".into(),
+        ApiType::String => "GString".into(),
+        ApiType::Array => "VarArray".into(),
+        ApiType::Dict => "VarDictionary".into(),
+        ApiType::Ext(name) => format!("Gd<{name}>"),
+        ApiType::Void | ApiType::Object | ApiType::LuaRef => "Variant".into(),
+    }
+}
+
+fn result_name(ty: &ApiType) -> String {
+    match ty {
+        ApiType::Void => "null".into(),
+        ApiType::Boolean => "a bool".into(),
+        ApiType::Integer => "an int".into(),
+        ApiType::Float => "a float".into(),
+        ApiType::String => "a String".into(),
+        ApiType::Array => "an Array".into(),
+        ApiType::Dict => "a Dictionary".into(),
+        ApiType::Ext(name) => format!("a {name}"),
+        ApiType::Object | ApiType::LuaRef => "any Variant".into(),
+    }
+}
+
+fn doc(out: &mut String, f: &ApiFunction) {
+    writeln!(
+        out,
+        "    /// Requests `{}`, added in API level {}. Its result is {}",
+        f.name,
+        f.since,
+        result_name(&f.return_type)
+    )
+    .unwrap();
+    if let Some(d) = f.deprecated_since {
+        writeln!(out, "    ///\n    /// Deprecated since API level {d}").unwrap();
+    }
+}
+
+fn main() {
+    println!("cargo::rerun-if-changed={API_INFO}");
+    let file = File::open(API_INFO).unwrap_or_else(|e| panic!("Couldn't open {API_INFO}: {e}"));
+    let info = ApiInfo::read(file).unwrap_or_else(|e| panic!("{API_INFO}: {e}"));
+    let functions: Vec<_> = info
+        .functions
+        .iter()
+        .filter(|f| f.name.starts_with("nvim_") && f.is_rpc_callable())
+        .collect();
+
+    let mut out = String::new();
+    writeln!(out, "/// The API level the bindings were generated for").unwrap();
+    writeln!(out, "pub const API_LEVEL: u64 = {};\n", info.api_level).unwrap();
+
+    writeln!(out, "/// The ext type ids of the `types` table").unwrap();
+    writeln!(out, "pub const EXT_TYPES: &[(&str, i8)] = &[").unwrap();
+    for t in &info.types {
+        writeln!(out, "    (\"{}\", {}),", t.name, t.id).unwrap();
+    }
+    writeln!(out, "];\n").unwrap();
+
+    writeln!(
+        out,
+        "/// The API level a function was added in, and the one it was deprecated in"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub fn api_levels(method: &str) -> Option<(u64, Option<u64>)> {{\n    match method {{"
+    )
+    .unwrap();
+    for f in &functions {
+        writeln!(
+            out,
+            "        \"{}\" => Some(({}, {:?})),",
+            f.name, f.since, f.deprecated_since
+        )
+        .unwrap();
+    }
+    writeln!(out, "        _ => None,\n    }}\n}}\n").unwrap();
+
+    writeln!(out, "pub trait NvimApi {{").unwrap();
+    for f in &functions {
+        doc(&mut out, f);
+        if let Some(d) = f.deprecated_since {
+            writeln!(
+                out,
+                "    #[deprecated = \"deprecated since API level {d}\"]"
+            )
+            .unwrap();
+        }
+        let params: String = f
+            .params
+            .iter()
+            .map(|p| format!(", {}: {}", ident(p), rust_type(&p.ty)))
+            .collect();
+        writeln!(out, "    fn {}(&mut self{params}) -> i32;", f.name).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "impl NvimApi for NeovimProcess {{").unwrap();
+    for f in &functions {
+        let params: String = f
+            .params
+            .iter()
+            .map(|p| format!(", {}: {}", ident(p), rust_type(&p.ty)))
+            .collect();
+        let args: Vec<_> = f
+            .params
+            .iter()
+            .map(|p| match p.ty {
+                ApiType::Array => format!("Value::Array({})", ident(p)),
+                ApiType::Dict => format!("Value::Map({})", ident(p)),
+                ApiType::Void | ApiType::Object | ApiType::LuaRef => ident(p),
+                _ => format!("{}.into()", ident(p)),
+            })
+            .collect();
+        writeln!(out, "    fn {}(&mut self{params}) -> i32 {{", f.name).unwrap();
+        writeln!(
+            out,
+            "        self.request(\"{}\", vec![{}])\n    }}",
+            f.name,
+            args.join(", ")
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    // the deprecated functions are still bound, and warn when they're called
+    writeln!(
+        out,
+        "#[godot_api(secondary)]\n#[allow(deprecated)]\nimpl NeovimClient {{"
+    )
+    .unwrap();
+    for f in &functions {
+        doc(&mut out, f);
+        let params: String = f
+            .params
+            .iter()
+            .map(|p| format!(", {}: {}", ident(p), godot_type(&p.ty)))
+            .collect();
+        writeln!(
+            out,
+            "    #[func]\n    pub fn {}(&mut self{params}) -> i32 {{",
+            f.name
+        )
+        .unwrap();
+        for p in &f.params {
+            let conversion = match p.ty {
+                ApiType::Array => "vararray_to_rpc",
+                ApiType::Dict => "dict_to_rpc",
+                ApiType::Void | ApiType::Object | ApiType::LuaRef => "godot_to_rmpv",
+                _ => continue,
+            };
+            let name = ident(p);
+            writeln!(
+                out,
+                "        let Some({name}) = argument(\"{}\", {conversion}({name})) else {{\n            return -1;\n        }};",
+                f.name
+            )
+            .unwrap();
+        }
+        let deprecated_since = match f.deprecated_since {
+            Some(d) => format!("Some({d})"),
+            None => "None".into(),
+        };
+        writeln!(
+            out,
+            "        let Some(np) = self.api_process(\"{}\", {}, {deprecated_since}) else {{\n            return -1;\n        }};",
+            f.name, f.since
+        )
+        .unwrap();
+        let args: String = f
+            .params
+            .iter()
+            .map(|p| match p.ty {
+                ApiType::String => format!(", &{}.to_string()", ident(p)),
+                ApiType::Ext(_) => format!(", &{}.bind()", ident(p)),
+                _ => format!(", {}", ident(p)),
+            })
+            .collect();
+        let args = args.trim_start_matches(", ");
+        writeln!(out, "        np.{}({args})\n    }}\n", f.name).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("nvim_api.rs");
+    fs::write(path, out).unwrap();
+}
@@ -0,0 +1,214 @@
+//! Neovim's API metadata, as dumped by `nvim --api-info` or returned by `nvim_get_api_info`,
+//! see ":h api-metadata"
+
+use std::io::Read;
+
+use rmpv::Value;
+
+use crate::err::VimdowError;
+
+/// The type of a parameter or return value, see ":h api-types"
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiType {
+    Void,
+    Boolean,
+    Integer,
+    Float,
+    String,
+    Array,
+    Dict,
+    Object,
+    /// One of the types of the `types` table, like `Buffer`
+    Ext(String),
+    /// Only usable from lua, not over rpc
+    LuaRef,
+}
+
+impl ApiType {
+    /// Parses the names in the metadata. `ArrayOf(Integer, 2)` is an array, `Dict(opts)` a
+    /// dict, and unions or names that newer versions add are objects
+    pub fn from_name(name: &str, types: &[ExtType]) -> Self {
+        let base = name.split('(').next().unwrap_or_default();
+        match base {
+            "void" => Self::Void,
+            "Boolean" => Self::Boolean,
+            "Integer" => Self::Integer,
+            "Float" => Self::Float,
+            "String" => Self::String,
+            "Array" | "ArrayOf" => Self::Array,
+            "Dict" | "Dictionary" | "DictOf" => Self::Dict,
+            "LuaRef" => Self::LuaRef,
+            _ if types.iter().any(|t| t.name == name) => Self::Ext(name.to_string()),
+            _ => Self::Object,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiParam {
+    pub name: String,
+    pub ty: ApiType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiFunction {
+    pub name: String,
+    pub params: Vec<ApiParam>,
+    pub return_type: ApiType,
+    /// The API level that added it
+    pub since: u64,
+    pub deprecated_since: Option<u64>,
+}
+
+impl ApiFunction {
+    /// Functions taking a `LuaRef` can only be called from lua
+    pub fn is_rpc_callable(&self) -> bool {
+        self.params.iter().all(|p| p.ty != ApiType::LuaRef)
+    }
+}
+
+/// An ext type of msgpack-rpc, like `Buffer`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtType {
+    pub name: String,
+    pub id: i8,
+    pub prefix: String,
+}
+
+/// An object of an ext type, like a buffer. msgpack-rpc sends it as the ext `ext` holding
+/// the msgpack encoded `id`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handle {
+    pub ext: i8,
+    pub id: i64,
+}
+
+impl Handle {
+    pub fn decode(ext: i8, data: &[u8]) -> Option<Self> {
+        let id = rmpv::decode::read_value(&mut &data[..]).ok()?.as_i64()?;
+        Some(Self { ext, id })
+    }
+}
+
+impl From<Handle> for Value {
+    fn from(handle: Handle) -> Self {
+        let mut data = vec![];
+        rmpv::encode::write_value(&mut data, &handle.id.into())
+            .expect("Writing to a Vec can't fail");
+        Value::Ext(handle.ext, data)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiInfo {
+    pub api_level: u64,
+    pub functions: Vec<ApiFunction>,
+    pub types: Vec<ExtType>,
+}
+
+fn malformed(why: &str) -> VimdowError {
+    VimdowError::Rpc(format!("Malformed api metadata: {why}"))
+}
+
+fn get<'a>(map: &'a Value, key: &str) -> Option<&'a Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, v)| v)
+}
+
+impl ApiInfo {
+    /// Reads the output of `nvim --api-info`
+    pub fn read(mut reader: impl Read) -> Result<Self, VimdowError> {
+        let metadata = rmpv::decode::read_value(&mut reader)
+            .map_err(|e| VimdowError::Rpc(format!("Undecodable api metadata: {e}")))?;
+        Self::parse(&metadata)
+    }
+
+    /// Parses the metadata map, which `nvim_get_api_info` returns along with the channel id
+    pub fn parse(metadata: &Value) -> Result<Self, VimdowError> {
+        let api_level = get(metadata, "version")
+            .and_then(|v| get(v, "api_level"))
+            .and_then(Value::as_u64)
+            .ok_or_else(|| malformed("no api_level"))?;
+
+        let mut types = vec![];
+        for (name, t) in get(metadata, "types")
+            .and_then(Value::as_map)
+            .ok_or_else(|| malformed("no types"))?
+        {
+            let name = name.as_str().ok_or_else(|| malformed("type name"))?;
+            let id = get(t, "id")
+                .and_then(Value::as_i64)
+                .and_then(|id| i8::try_from(id).ok())
+                .ok_or_else(|| malformed(&format!("id of {name}")))?;
+            let prefix = get(t, "prefix").and_then(Value::as_str).unwrap_or_default();
+            types.push(ExtType {
+                name: name.to_string(),
+                id,
+                prefix: prefix.to_string(),
+            });
+        }
+
+        let mut functions = vec![];
+        for f in get(metadata, "functions")
+            .and_then(Value::as_array)
+            .ok_or_else(|| malformed("no functions"))?
+        {
+            let name = get(f, "name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| malformed("function name"))?;
+            // `vim.mpack` encodes an empty list as a map
+            let parameters = match get(f, "parameters") {
+                Some(Value::Array(p)) => p.as_slice(),
+                Some(Value::Map(m)) if m.is_empty() => &[],
+                _ => return Err(malformed(&format!("parameters of {name}"))),
+            };
+            let mut params = vec![];
+            for p in parameters {
+                let [ty, pname] = p.as_array().map(Vec::as_slice).unwrap_or_default() else {
+                    return Err(malformed(&format!("parameter of {name}")));
+                };
+                let (Some(ty), Some(pname)) = (ty.as_str(), pname.as_str()) else {
+                    return Err(malformed(&format!("parameter of {name}")));
+                };
+                params.push(ApiParam {
+                    name: pname.to_string(),
+                    ty: ApiType::from_name(ty, &types),
+                });
+            }
+            let return_type = get(f, "return_type")
+                .and_then(Value::as_str)
+                .ok_or_else(|| malformed(&format!("return type of {name}")))?;
+            functions.push(ApiFunction {
+                name: name.to_string(),
+                params,
+                return_type: ApiType::from_name(return_type, &types),
+                since: get(f, "since").and_then(Value::as_u64).unwrap_or_default(),
+                deprecated_since: get(f, "deprecated_since").and_then(Value::as_u64),
+            });
+        }
+
+        Ok(Self {
+            api_level,
+            functions,
+            types,
+        })
+    }
+
+    pub fn function(&self, name: &str) -> Option<&ApiFunction> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    pub fn ext_type(&self, name: &str) -> Option<&ExtType> {
+        self.types.iter().find(|t| t.name == name)
+    }
+
+    /// The handle of an object of the ext type called `name`
+    pub fn handle(&self, name: &str, id: i64) -> Option<Handle> {
+        Some(Handle {
+            ext: self.ext_type(name)?.id,
+            id,
+        })
+    }
+}
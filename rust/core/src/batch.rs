@@ -0,0 +1,80 @@
+//! Several API calls sent in one round trip, see ":h nvim_call_atomic()"
+
+use rmpv::Value;
+
+use crate::err::VimdowError;
+
+/// API calls that neovim runs one after the other, stopping at the first one that fails
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Batch {
+    calls: Vec<Value>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, method: &str, params: Vec<Value>) -> &mut Self {
+        self.calls
+            .push(Value::Array(vec![method.into(), Value::Array(params)]));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// The params of the `nvim_call_atomic` request
+    pub fn into_params(self) -> Vec<Value> {
+        vec![Value::Array(self.calls)]
+    }
+}
+
+/// The call that failed, the ones after it weren't run
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchError {
+    pub index: usize,
+    /// Neovim's error type, 0 for an exception and 1 for a validation error
+    pub kind: i64,
+    pub message: String,
+}
+
+/// What neovim answered to a batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResult {
+    /// The results of the calls that ran, in order
+    pub results: Vec<Value>,
+    pub error: Option<BatchError>,
+}
+
+impl BatchResult {
+    /// Reads the result of an `nvim_call_atomic` request, which is `[results, error]`
+    pub fn parse(result: &Value) -> Result<Self, VimdowError> {
+        let malformed = || VimdowError::Rpc(format!("invalid nvim_call_atomic result: {result}"));
+        let Some([results, error]) = result.as_array().map(Vec::as_slice) else {
+            return Err(malformed());
+        };
+        let results = results.as_array().cloned().ok_or_else(malformed)?;
+        let error = match error {
+            Value::Nil => None,
+            Value::Array(e) => match e.as_slice() {
+                [index, kind, message] => Some(BatchError {
+                    index: index
+                        .as_u64()
+                        .and_then(|i| usize::try_from(i).ok())
+                        .ok_or_else(malformed)?,
+                    kind: kind.as_i64().ok_or_else(malformed)?,
+                    message: message.as_str().ok_or_else(malformed)?.to_string(),
+                }),
+                _ => return Err(malformed()),
+            },
+            _ => return Err(malformed()),
+        };
+        Ok(Self { results, error })
+    }
+}
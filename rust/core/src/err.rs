@@ -0,0 +1,14 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum VimdowError {
+    #[error("IO Error: {0}")]
+    IO(io::Error),
+    #[error("Malformed session file: {0}")]
+    Session(String),
+    #[error("Malformed rpc message: {0}")]
+    Rpc(String),
+    #[error("Neovim didn't make a request with msgid {0}, or it was already answered")]
+    UnknownRequest(u32),
+}
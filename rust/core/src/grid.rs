@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rmpv::Value;
+
+use crate::redraw::{GridLineCell, RedrawEvent};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    pub text: String,
+    pub hl_id: u64,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            text: " ".into(),
+            hl_id: 0,
+        }
+    }
+}
+
+/// The cells of a grid, as drawn by the `grid_*` events
+#[derive(Debug, Clone, Default)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    // row major
+    cells: Vec<Cell>,
+}
+
+impl Grid {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn cell(&self, row: usize, col: usize) -> Option<&Cell> {
+        if col >= self.width {
+            return None;
+        }
+        self.cells.get(row * self.width + col)
+    }
+
+    /// The text of a row. The right half of a double width character is an
+    /// empty cell, so the text can be shorter than the grid is wide
+    pub fn row_text(&self, row: usize) -> String {
+        (0..self.width)
+            .filter_map(|col| self.cell(row, col))
+            .map(|c| c.text.as_str())
+            .collect()
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        let mut cells = vec![Cell::default(); width * height];
+        for row in 0..height.min(self.height) {
+            for col in 0..width.min(self.width) {
+                cells[row * width + col] = self.cells[row * self.width + col].clone();
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.cells = cells;
+    }
+
+    fn clear(&mut self) {
+        self.cells.fill(Cell::default());
+    }
+
+    fn put_line(&mut self, row: usize, col_start: usize, cells: &[GridLineCell]) {
+        if row >= self.height {
+            return;
+        }
+        let mut col = col_start;
+        let mut hl_id = 0;
+        for cell in cells {
+            hl_id = cell.hl_id.unwrap_or(hl_id);
+            for _ in 0..cell.repeat {
+                if col >= self.width {
+                    return;
+                }
+                self.cells[row * self.width + col] = Cell {
+                    text: cell.text.clone(),
+                    hl_id,
+                };
+                col += 1;
+            }
+        }
+    }
+
+    // moves the region's rows up by `rows`, or down if it's negative.
+    // the rows that are left behind are redrawn by neovim
+    fn scroll(&mut self, top: usize, bot: usize, left: usize, right: usize, rows: i64) {
+        let bot = bot.min(self.height);
+        let right = right.min(self.width);
+        let width = self.width;
+        let copy_row = |cells: &mut Vec<Cell>, to: usize, from: usize| {
+            for col in left..right {
+                cells[to * width + col] = cells[from * width + col].clone();
+            }
+        };
+
+        let shift = rows.unsigned_abs() as usize;
+        if rows > 0 {
+            for row in top..bot.saturating_sub(shift) {
+                copy_row(&mut self.cells, row, row + shift);
+            }
+        } else {
+            for row in (top + shift..bot).rev() {
+                copy_row(&mut self.cells, row, row - shift);
+            }
+        }
+    }
+}
+
+/// Everything a linegrid UI knows after applying `redraw` events
+#[derive(Debug, Default)]
+pub struct Ui {
+    grids: BTreeMap<u64, Grid>,
+    hl_attrs: HashMap<u64, Vec<(String, Value)>>,
+    // grid, row and column
+    cursor: (u64, u64, u64),
+    default_colors: (i64, i64, i64),
+    flushes: u64,
+}
+
+impl Ui {
+    pub fn apply(&mut self, event: &RedrawEvent) {
+        match event {
+            RedrawEvent::GridResize {
+                grid,
+                width,
+                height,
+            } => self
+                .grids
+                .entry(*grid)
+                .or_default()
+                .resize(*width as usize, *height as usize),
+            RedrawEvent::GridClear { grid } => {
+                if let Some(g) = self.grids.get_mut(grid) {
+                    g.clear();
+                }
+            }
+            RedrawEvent::GridDestroy { grid } => {
+                self.grids.remove(grid);
+            }
+            RedrawEvent::GridCursorGoto { grid, row, col } => self.cursor = (*grid, *row, *col),
+            RedrawEvent::GridLine {
+                grid,
+                row,
+                col_start,
+                cells,
+            } => {
+                if let Some(g) = self.grids.get_mut(grid) {
+                    g.put_line(*row as usize, *col_start as usize, cells);
+                }
+            }
+            RedrawEvent::GridScroll {
+                grid,
+                top,
+                bot,
+                left,
+                right,
+                rows,
+            } => {
+                if let Some(g) = self.grids.get_mut(grid) {
+                    g.scroll(
+                        *top as usize,
+                        *bot as usize,
+                        *left as usize,
+                        *right as usize,
+                        *rows,
+                    );
+                }
+            }
+            RedrawEvent::HlAttrDefine { id, rgb_attrs } => {
+                self.hl_attrs.insert(*id, rgb_attrs.clone());
+            }
+            RedrawEvent::DefaultColorsSet { fg, bg, sp } => self.default_colors = (*fg, *bg, *sp),
+            RedrawEvent::Flush => self.flushes += 1,
+            RedrawEvent::Other(_) => {}
+        }
+    }
+
+    pub fn grid(&self, id: u64) -> Option<&Grid> {
+        self.grids.get(&id)
+    }
+
+    /// The grid, row and column of the cursor
+    pub fn cursor(&self) -> (u64, u64, u64) {
+        self.cursor
+    }
+
+    /// The rgb attributes of a highlight, id 0 being the default colors without any
+    pub fn hl_attr(&self, id: u64) -> &[(String, Value)] {
+        self.hl_attrs
+            .get(&id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// A highlight's attribute, like "foreground" or "bold"
+    pub fn hl_attr_value(&self, id: u64, key: &str) -> Option<&Value> {
+        self.hl_attr(id)
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn default_colors(&self) -> (i64, i64, i64) {
+        self.default_colors
+    }
+
+    /// How many times neovim finished a screen update
+    pub fn flushes(&self) -> u64 {
+        self.flushes
+    }
+}
@@ -0,0 +1,78 @@
+use bitflags::bitflags;
+
+bitflags! {
+    pub struct Modifiers: u8 {
+        const NONE = 0;
+        const CTRL = 1;
+        const ALT = 1 << 1;
+        const SHIFT = 1 << 2;
+        const META = 1 << 3;
+    }
+}
+
+/// A key as neovim names it, see ":h key-notation"
+pub enum NvimKeycode {
+    Named(&'static str),
+    Function(String),
+    Printable(char),
+    /// A key neovim has no name for, like a media key. Only sent along with modifiers
+    Unnamed(String),
+}
+
+/// A key press, encoded for `nvim_input` by its `Display` impl
+pub struct NvimInput {
+    mods: Modifiers,
+    nk: NvimKeycode,
+}
+
+impl NvimInput {
+    pub fn new(mods: Modifiers, nk: NvimKeycode) -> Self {
+        Self { mods, nk }
+    }
+
+    /// A printable character, `<` being escaped
+    pub fn printable(mods: Modifiers, c: char) -> Self {
+        let nk = if c == '<' {
+            NvimKeycode::Named("lt")
+        } else {
+            NvimKeycode::Printable(c)
+        };
+        Self::new(mods, nk)
+    }
+
+    pub fn apply_modifiers(&self, s: &str) -> String {
+        let mut out = String::from("<");
+        for (n, _) in self.mods.iter_names() {
+            out.push(n.chars().nth(0).unwrap());
+            out.push('-');
+        }
+        out.push_str(s);
+        out.push('>');
+        out
+    }
+}
+
+impl std::fmt::Display for NvimInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let out = match self.nk {
+            NvimKeycode::Printable(c) => {
+                let c = c.to_string();
+                if self.mods.is_empty() {
+                    c
+                } else {
+                    self.apply_modifiers(&c)
+                }
+            }
+            NvimKeycode::Unnamed(ref k) => {
+                if self.mods.is_empty() {
+                    format!(":lua vim.print(\"couldn't represent keycode: {}\")<CR>", k)
+                } else {
+                    self.apply_modifiers(k)
+                }
+            }
+            NvimKeycode::Named(n) => self.apply_modifiers(n),
+            NvimKeycode::Function(ref f) => self.apply_modifiers(f),
+        };
+        f.write_str(&out)
+    }
+}
@@ -0,0 +1,16 @@
+//! The parts of vimdow's neovim client that don't need godot: the msgpack-rpc session with
+//! an embedded neovim, the `redraw` events and the grids they draw, and key notation.
+//! The vimdow GDExtension binds these to godot
+
+pub mod api_info;
+pub mod batch;
+pub mod err;
+pub mod grid;
+pub mod keys;
+pub mod log;
+pub mod paste;
+pub mod process;
+pub mod redraw;
+pub mod requests;
+pub mod rpc;
+pub mod session;
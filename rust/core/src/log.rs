@@ -0,0 +1,29 @@
+//! Messages from code that also runs without godot, like the neovim process driven by tests.
+//! Godot's output is used once the extension sets it with `set_log`, stderr before that
+
+use std::sync::OnceLock;
+
+pub trait Log: Send + Sync {
+    fn error(&self, msg: &str);
+    fn info(&self, msg: &str);
+}
+
+static LOG: OnceLock<Box<dyn Log>> = OnceLock::new();
+
+pub fn set_log(log: Box<dyn Log>) {
+    let _ = LOG.set(log);
+}
+
+pub fn error(msg: &str) {
+    match LOG.get() {
+        Some(log) => log.error(msg),
+        None => eprintln!("ERROR: {msg}"),
+    }
+}
+
+pub fn info(msg: &str) {
+    match LOG.get() {
+        Some(log) => log.info(msg),
+        None => eprintln!("{msg}"),
+    }
+}
@@ -0,0 +1,27 @@
+//! Splitting large pastes into the chunks streamed by `nvim_paste`
+
+/// Max bytes sent per `nvim_paste` call
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Splits text into chunks of at most `size` bytes, preferring to break after a newline
+/// so that a CRLF is never split between two calls. `size` has to fit any character
+pub fn paste_chunks(mut text: &str, size: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    while text.len() > size {
+        let mut end = size;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        if let Some(nl) = text[..end].rfind('\n') {
+            end = nl + 1;
+        } else if end > 1 && text[..end].ends_with('\r') && text[end..].starts_with('\n') {
+            // a window without a newline can still end between the two
+            end -= 1;
+        }
+        let (chunk, rest) = text.split_at(end);
+        chunks.push(chunk);
+        text = rest;
+    }
+    chunks.push(text);
+    chunks
+}
@@ -0,0 +1,349 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use rmpv::Value;
+
+use crate::batch::Batch;
+use crate::err::VimdowError;
+use crate::log;
+use crate::rpc::Message;
+use crate::session::{Direction, Recorder, TeeReader, read_session};
+
+pub struct NeovimProcess {
+    // missing when a session is replayed or neovim was connected to with `connect`
+    child: Option<Child>,
+    replaying: bool,
+    // false once neovim's end of the stream closed
+    connected: bool,
+    shutdown: Arc<AtomicBool>,
+    _from_handle: JoinHandle<()>,
+    _to_handle: JoinHandle<()>,
+    // the receiver that takes the decoded mspack values
+    from: mpsc::Receiver<Value>,
+    // the sender that writes encoded mspack values
+    to: mpsc::Sender<Vec<u8>>,
+
+    msgid: u32,
+
+    // requests sent by the client waiting for responses
+    pending_requests: HashSet<u32>,
+
+    // requests sent by the server waiting for responses
+    pending_responses: HashSet<u32>,
+}
+
+impl NeovimProcess {
+    pub fn new(program: &str, nvim_args: &[impl AsRef<OsStr>]) -> Result<Self, VimdowError> {
+        Self::spawn(program, nvim_args, None)
+    }
+
+    /// Starts neovim, recording everything sent both ways into a session file
+    pub fn new_recorded(
+        program: &str,
+        nvim_args: &[impl AsRef<OsStr>],
+        session_path: impl AsRef<Path>,
+    ) -> Result<Self, VimdowError> {
+        Self::spawn(program, nvim_args, Some(Recorder::create(session_path)?))
+    }
+
+    fn spawn(
+        program: &str,
+        nvim_args: &[impl AsRef<OsStr>],
+        recorder: Option<Recorder>,
+    ) -> Result<Self, VimdowError> {
+        let mut child_builder = Command::new(program);
+        child_builder.stdin(Stdio::piped()).stdout(Stdio::piped());
+        for arg in nvim_args {
+            child_builder.arg(arg);
+        }
+
+        let mut child = child_builder.spawn().map_err(VimdowError::IO)?;
+        let stdin = child.stdin.take().expect("Stdin is not available");
+        let stdout = child.stdout.take().expect("Stdout is not available");
+
+        let mut np = Self::connect_recorded(stdout, stdin, recorder);
+        np.child = Some(child);
+        Ok(np)
+    }
+
+    /// Talks to a neovim that reads from `writer` and writes to `reader`,
+    /// like the one `nvim --embed` starts or a fake one in tests
+    pub fn connect(
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+    ) -> Self {
+        Self::connect_recorded(reader, writer, None)
+    }
+
+    fn connect_recorded(
+        reader: impl Read + Send + 'static,
+        mut writer: impl Write + Send + 'static,
+        recorder: Option<Recorder>,
+    ) -> Self {
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let (to, recv_in_process) = mpsc::channel::<Vec<u8>>();
+        let shutdown_to = shutdown.clone();
+        let recorder_to = recorder.clone();
+        let to_handle = thread::spawn(move || {
+            while !shutdown_to.load(Ordering::Relaxed) {
+                let Ok(buf) = recv_in_process.recv() else {
+                    break;
+                };
+                if let Some(recorder) = &recorder_to {
+                    recorder.record(Direction::ToNeovim, &buf);
+                }
+                if let Err(e) = writer.write_all(&buf[..]).and_then(|_| writer.flush()) {
+                    log::error(&format!("Couldn't write to neovim: {e}"));
+                    break;
+                }
+            }
+        });
+
+        let (send_from_process, from) = mpsc::channel();
+        let mut reader = TeeReader::new(reader);
+        let shutdown_from = shutdown.clone();
+        let from_handle = thread::spawn(move || {
+            while !shutdown_from.load(Ordering::Relaxed) {
+                // the stream can't be followed past a value that can't be read,
+                // which is also how neovim exiting shows up
+                let value = match rmpv::decode::read_value(&mut reader) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        if !shutdown_from.load(Ordering::Relaxed) {
+                            log::info(&format!("Stopped reading from neovim: {e}"));
+                        }
+                        break;
+                    }
+                };
+                if let Some(recorder) = &recorder {
+                    recorder.record(Direction::FromNeovim, &reader.read);
+                }
+                reader.read.clear();
+                if send_from_process.send(value).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            child: None,
+            replaying: false,
+            connected: true,
+            shutdown,
+            to,
+            from,
+            _from_handle: from_handle,
+            _to_handle: to_handle,
+            msgid: 0,
+            pending_requests: HashSet::new(),
+            pending_responses: HashSet::new(),
+        }
+    }
+
+    /// Plays back what neovim sent in a session file, at the pace it was recorded.
+    /// Everything sent to it is dropped, and it keeps running after the last message
+    pub fn replay(session_path: impl AsRef<Path>) -> Result<Self, VimdowError> {
+        let entries = read_session(session_path)?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let (to, recv_in_process) = mpsc::channel::<Vec<u8>>();
+        let to_handle = thread::spawn(move || while recv_in_process.recv().is_ok() {});
+
+        let (send_from_process, from) = mpsc::channel();
+        let shutdown_from = shutdown.clone();
+        let from_handle = thread::spawn(move || {
+            let start = Instant::now();
+            for entry in entries {
+                if entry.direction != Direction::FromNeovim {
+                    continue;
+                }
+                thread::sleep(entry.time.saturating_sub(start.elapsed()));
+                if shutdown_from.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let value = match entry.value() {
+                    Ok(value) => value,
+                    Err(e) => {
+                        log::error(&e.to_string());
+                        break;
+                    }
+                };
+                if send_from_process.send(value).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child: None,
+            replaying: true,
+            connected: true,
+            shutdown,
+            to,
+            from,
+            _from_handle: from_handle,
+            _to_handle: to_handle,
+            msgid: 0,
+            pending_requests: HashSet::new(),
+            pending_responses: HashSet::new(),
+        })
+    }
+
+    /// The next message from neovim, if one arrived
+    pub fn check(&mut self) -> Option<Message> {
+        loop {
+            match self.from.try_recv() {
+                Ok(v) => {
+                    if let Some(message) = self.track(v) {
+                        return Some(message);
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for the next message from neovim
+    pub fn wait(&mut self, timeout: Duration) -> Option<Message> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.from.recv_timeout(left) {
+                Ok(v) => {
+                    if let Some(message) = self.track(v) {
+                        return Some(message);
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => return None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    self.connected = false;
+                    return None;
+                }
+            }
+        }
+    }
+
+    // keeps track of the requests waiting on a response,
+    // messages that aren't msgpack-rpc are logged and dropped
+    fn track(&mut self, v: Value) -> Option<Message> {
+        let message = match Message::parse(v) {
+            Ok(message) => message,
+            Err(e) => {
+                log::error(&e.to_string());
+                return None;
+            }
+        };
+
+        match message {
+            Message::Request { msgid, .. } => {
+                self.pending_responses.insert(msgid);
+            }
+            // a replayed session answers the requests of the recorded client
+            Message::Response { msgid, .. } => {
+                if !self.pending_requests.remove(&msgid) && !self.replaying {
+                    log::error(&format!(
+                        "Neovim responded to msgid {msgid}, which wasn't requested"
+                    ));
+                }
+            }
+            Message::Notification { .. } => {}
+        }
+        Some(message)
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        let Some(child) = self.child.as_mut() else {
+            return self.replaying || self.connected;
+        };
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                log::info(&format!("Neovim process exited with: {status}"));
+                false
+            }
+            Ok(None) => true,
+            Err(e) => {
+                log::error(&format!("Neovim process is dead: {e}"));
+                false
+            }
+        }
+    }
+
+    fn send(&mut self, message: Message) {
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, &message.into()).expect("Couldn't serialize message");
+        if let Err(se) = self.to.send(buf) {
+            log::error(&se.to_string());
+        }
+    }
+
+    pub fn request(&mut self, method: &str, params: Vec<Value>) -> i32 {
+        let msgid = self.msgid;
+        self.msgid += 1;
+        self.pending_requests.insert(msgid);
+        self.send(Message::Request {
+            msgid,
+            method: method.to_string(),
+            params,
+        });
+        msgid as i32
+    }
+
+    /// Sends the calls of a batch as one `nvim_call_atomic` request, its result can be read
+    /// with `BatchResult::parse`
+    pub fn batch(&mut self, batch: Batch) -> i32 {
+        self.request("nvim_call_atomic", batch.into_params())
+    }
+
+    /// Answers a request from neovim, each one only once
+    pub fn respond(&mut self, msgid: u32, error: Value, result: Value) -> Result<(), VimdowError> {
+        if !self.pending_responses.remove(&msgid) {
+            return Err(VimdowError::UnknownRequest(msgid));
+        }
+        self.send(Message::Response {
+            msgid,
+            error,
+            result,
+        });
+        Ok(())
+    }
+
+    /// Answers a request nothing handles, neovim would otherwise wait on it forever
+    pub fn reject(&mut self, msgid: u32, method: &str) -> Result<(), VimdowError> {
+        self.respond(
+            msgid,
+            format!("Unknown method: {method}").into(),
+            Value::Nil,
+        )
+    }
+
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        match self.child.as_mut() {
+            Some(child) => child.try_wait(),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Drop for NeovimProcess {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+
+        if let Some(child) = self.child.as_mut() {
+            let _ecode = child.kill();
+            let _ecode = child.wait();
+        }
+    }
+}
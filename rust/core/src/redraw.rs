@@ -0,0 +1,142 @@
+use rmpv::Value;
+
+/// A cell of a `grid_line` event, see ":h ui-event-grid_line"
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridLineCell {
+    pub text: String,
+    // the highlight of the previous cell is kept when this is missing
+    pub hl_id: Option<u64>,
+    pub repeat: u64,
+}
+
+/// The `redraw` events of the linegrid UI that vimdow draws, see ":h ui-linegrid"
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedrawEvent {
+    GridResize {
+        grid: u64,
+        width: u64,
+        height: u64,
+    },
+    GridClear {
+        grid: u64,
+    },
+    GridDestroy {
+        grid: u64,
+    },
+    GridCursorGoto {
+        grid: u64,
+        row: u64,
+        col: u64,
+    },
+    GridLine {
+        grid: u64,
+        row: u64,
+        col_start: u64,
+        cells: Vec<GridLineCell>,
+    },
+    GridScroll {
+        grid: u64,
+        top: u64,
+        bot: u64,
+        left: u64,
+        right: u64,
+        rows: i64,
+    },
+    HlAttrDefine {
+        id: u64,
+        rgb_attrs: Vec<(String, Value)>,
+    },
+    DefaultColorsSet {
+        fg: i64,
+        bg: i64,
+        sp: i64,
+    },
+    Flush,
+    /// Events vimdow doesn't model, by name
+    Other(String),
+}
+
+fn u(v: &Value) -> Option<u64> {
+    v.as_u64()
+}
+
+fn i(v: &Value) -> Option<i64> {
+    v.as_i64()
+}
+
+fn cell(v: &Value) -> Option<GridLineCell> {
+    let [text, rest @ ..] = v.as_array()?.as_slice() else {
+        return None;
+    };
+    Some(GridLineCell {
+        text: text.as_str()?.to_string(),
+        hl_id: rest.first().and_then(u),
+        repeat: rest.get(1).and_then(u).unwrap_or(1),
+    })
+}
+
+impl RedrawEvent {
+    fn parse(name: &str, args: &[Value]) -> Option<Self> {
+        Some(match (name, args) {
+            ("grid_resize", [grid, width, height, ..]) => Self::GridResize {
+                grid: u(grid)?,
+                width: u(width)?,
+                height: u(height)?,
+            },
+            ("grid_clear", [grid, ..]) => Self::GridClear { grid: u(grid)? },
+            ("grid_destroy", [grid, ..]) => Self::GridDestroy { grid: u(grid)? },
+            ("grid_cursor_goto", [grid, row, col, ..]) => Self::GridCursorGoto {
+                grid: u(grid)?,
+                row: u(row)?,
+                col: u(col)?,
+            },
+            ("grid_line", [grid, row, col_start, Value::Array(cells), ..]) => Self::GridLine {
+                grid: u(grid)?,
+                row: u(row)?,
+                col_start: u(col_start)?,
+                cells: cells.iter().map(cell).collect::<Option<_>>()?,
+            },
+            ("grid_scroll", [grid, top, bot, left, right, rows, ..]) => Self::GridScroll {
+                grid: u(grid)?,
+                top: u(top)?,
+                bot: u(bot)?,
+                left: u(left)?,
+                right: u(right)?,
+                rows: i(rows)?,
+            },
+            ("hl_attr_define", [id, Value::Map(rgb_attrs), ..]) => Self::HlAttrDefine {
+                id: u(id)?,
+                rgb_attrs: rgb_attrs
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v.clone())))
+                    .collect(),
+            },
+            ("default_colors_set", [fg, bg, sp, ..]) => Self::DefaultColorsSet {
+                fg: i(fg)?,
+                bg: i(bg)?,
+                sp: i(sp)?,
+            },
+            ("flush", _) => Self::Flush,
+            _ => Self::Other(name.to_string()),
+        })
+    }
+}
+
+/// Decodes the params of a `redraw` notification. Each of them is an event name followed
+/// by the arguments of every time it happened. Malformed events are skipped
+pub fn parse_redraw(params: &[Value]) -> Vec<RedrawEvent> {
+    let mut events = vec![];
+    for batch in params {
+        let Some([name, calls @ ..]) = batch.as_array().map(Vec::as_slice) else {
+            continue;
+        };
+        let Some(name) = name.as_str() else {
+            continue;
+        };
+        for args in calls {
+            let args = args.as_array().map(Vec::as_slice).unwrap_or_default();
+            events.extend(RedrawEvent::parse(name, args));
+        }
+    }
+    events
+}
@@ -0,0 +1,33 @@
+//! Deciding who answers the requests neovim sends to the client
+
+use std::collections::HashMap;
+
+/// Methods of the clipboard provider, which the client answers itself
+pub const CLIPBOARD_METHODS: [&str; 2] = ["vimdow_clipboard_set", "vimdow_clipboard_get"];
+
+/// Who answers a request from neovim
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// The client's clipboard
+    Clipboard,
+    /// The handler registered for the method
+    Handler,
+    /// Whatever listens to the client's requests
+    Listeners,
+    /// Nothing does, neovim gets an error from `NeovimProcess::reject`
+    Reject,
+}
+
+/// Routes a request for `method`, checking the clipboard first, then the registered
+/// `handlers`, then whether anything listens to requests at all
+pub fn route<H>(method: &str, handlers: &HashMap<String, H>, has_listeners: bool) -> Route {
+    if CLIPBOARD_METHODS.contains(&method) {
+        Route::Clipboard
+    } else if handlers.contains_key(method) {
+        Route::Handler
+    } else if has_listeners {
+        Route::Listeners
+    } else {
+        Route::Reject
+    }
+}
@@ -0,0 +1,95 @@
+use rmpv::Value;
+
+use crate::err::VimdowError;
+
+/// A msgpack-rpc message, see ":h msgpack-rpc"
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Request {
+        msgid: u32,
+        method: String,
+        params: Vec<Value>,
+    },
+    Response {
+        msgid: u32,
+        error: Value,
+        result: Value,
+    },
+    Notification {
+        method: String,
+        params: Vec<Value>,
+    },
+}
+
+fn malformed(v: &Value, why: &str) -> VimdowError {
+    VimdowError::Rpc(format!("{why}: {v}"))
+}
+
+impl Message {
+    /// Classifies a value read from the stream, which can be anything
+    pub fn parse(v: Value) -> Result<Self, VimdowError> {
+        let Some(fields) = v.as_array() else {
+            return Err(malformed(&v, "not an array"));
+        };
+        let msgid = |id: &Value| {
+            id.as_u64()
+                .and_then(|id| u32::try_from(id).ok())
+                .ok_or_else(|| malformed(&v, "invalid msgid"))
+        };
+        let method = |method: &Value| {
+            method
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| malformed(&v, "invalid method name"))
+        };
+        let params = |params: &Value| {
+            params
+                .as_array()
+                .cloned()
+                .ok_or_else(|| malformed(&v, "params aren't an array"))
+        };
+
+        match fields.as_slice() {
+            [t, id, m, p] if t.as_u64() == Some(0) => Ok(Self::Request {
+                msgid: msgid(id)?,
+                method: method(m)?,
+                params: params(p)?,
+            }),
+            [t, id, error, result] if t.as_u64() == Some(1) => Ok(Self::Response {
+                msgid: msgid(id)?,
+                error: error.clone(),
+                result: result.clone(),
+            }),
+            [t, m, p] if t.as_u64() == Some(2) => Ok(Self::Notification {
+                method: method(m)?,
+                params: params(p)?,
+            }),
+            _ => Err(malformed(&v, "not a request, response or notification")),
+        }
+    }
+}
+
+impl From<Message> for Value {
+    fn from(message: Message) -> Self {
+        match message {
+            Message::Request {
+                msgid,
+                method,
+                params,
+            } => Value::Array(vec![
+                0.into(),
+                msgid.into(),
+                method.into(),
+                Value::Array(params),
+            ]),
+            Message::Response {
+                msgid,
+                error,
+                result,
+            } => Value::Array(vec![1.into(), msgid.into(), error, result]),
+            Message::Notification { method, params } => {
+                Value::Array(vec![2.into(), method.into(), Value::Array(params)])
+            }
+        }
+    }
+}
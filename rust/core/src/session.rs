@@ -0,0 +1,132 @@
+//! Recordings of the msgpack stream between vimdow and neovim, for bug reports and tests.
+//! A session file is a sequence of msgpack arrays `[direction, micros, bytes]`, where
+//! direction is 0 for what neovim sent and 1 for what was sent to it, micros is the time
+//! since the recording started and bytes is the message exactly as it was written
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rmpv::Value;
+
+use crate::err::VimdowError;
+use crate::log;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    FromNeovim = 0,
+    ToNeovim = 1,
+}
+
+/// A message of a session
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub direction: Direction,
+    pub time: Duration,
+    pub bytes: Vec<u8>,
+}
+
+impl Entry {
+    pub fn value(&self) -> Result<Value, VimdowError> {
+        rmpv::decode::read_value(&mut self.bytes.as_slice())
+            .map_err(|e| VimdowError::Session(format!("undecodable message: {e}")))
+    }
+
+    fn from_value(v: Value) -> Option<Self> {
+        let [direction, micros, Value::Binary(bytes)] = v.as_array()?.as_slice() else {
+            return None;
+        };
+        let direction = match direction.as_u64()? {
+            0 => Direction::FromNeovim,
+            1 => Direction::ToNeovim,
+            _ => return None,
+        };
+        Some(Self {
+            direction,
+            time: Duration::from_micros(micros.as_u64()?),
+            bytes: bytes.clone(),
+        })
+    }
+}
+
+/// Writes a session file, shared by the threads that talk to neovim
+#[derive(Clone)]
+pub struct Recorder {
+    start: Instant,
+    file: Arc<Mutex<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, VimdowError> {
+        let file = File::create(path).map_err(VimdowError::IO)?;
+        Ok(Self {
+            start: Instant::now(),
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub fn record(&self, direction: Direction, bytes: &[u8]) {
+        let entry = Value::Array(vec![
+            (direction as u8).into(),
+            (self.start.elapsed().as_micros() as u64).into(),
+            Value::Binary(bytes.to_vec()),
+        ]);
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, &entry).expect("Couldn't serialize session entry");
+
+        // written whole, so a crash leaves every message before it readable
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_all(&buf) {
+            log::error(&format!("Couldn't record the neovim session: {e}"));
+        }
+    }
+}
+
+/// Reads every entry of a session file
+pub fn read_session(path: impl AsRef<Path>) -> Result<Vec<Entry>, VimdowError> {
+    read_entries(File::open(path).map_err(VimdowError::IO)?)
+}
+
+/// Reads the entries of a session until the end of the stream
+pub fn read_entries(reader: impl Read) -> Result<Vec<Entry>, VimdowError> {
+    let mut reader = BufReader::new(reader);
+    let mut entries = vec![];
+    while !reader.fill_buf().map_err(VimdowError::IO)?.is_empty() {
+        let v = rmpv::decode::read_value(&mut reader).map_err(|e| {
+            VimdowError::Session(format!("entry {} can't be read: {e}", entries.len()))
+        })?;
+        let entry = Entry::from_value(v).ok_or_else(|| {
+            VimdowError::Session(format!(
+                "entry {} isn't [direction, micros, bytes]",
+                entries.len()
+            ))
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Keeps the bytes read from a stream, to record messages exactly as they were sent
+pub(crate) struct TeeReader<R> {
+    inner: R,
+    pub read: Vec<u8>,
+}
+
+impl<R> TeeReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            read: Vec::new(),
+        }
+    }
+}
+
+impl<R: Read> Read for TeeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
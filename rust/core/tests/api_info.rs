@@ -0,0 +1,88 @@
+//! Reads the API metadata that the bindings of the vimdow crate are generated from
+
+use std::fs::File;
+use std::process::Command;
+
+use rmpv::Value;
+use vimdow_core::api_info::{ApiInfo, ApiType, Handle};
+
+const API_INFO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../api/api-info.msgpack");
+
+fn checked_in() -> ApiInfo {
+    ApiInfo::read(File::open(API_INFO).unwrap()).unwrap()
+}
+
+#[test]
+fn checked_in_api_info_is_read() {
+    let info = checked_in();
+    let ids: Vec<_> = info.types.iter().map(|t| (t.name.as_str(), t.id)).collect();
+    assert_eq!(ids, [("Buffer", 0), ("Window", 1), ("Tabpage", 2)]);
+
+    let f = info.function("nvim_win_set_cursor").unwrap();
+    let params: Vec<_> = f.params.iter().map(|p| (p.name.as_str(), &p.ty)).collect();
+    assert_eq!(
+        params,
+        [
+            ("window", &ApiType::Ext("Window".into())),
+            ("pos", &ApiType::Array)
+        ]
+    );
+    assert_eq!(f.return_type, ApiType::Void);
+
+    let f = info.function("nvim_cmd").unwrap();
+    assert_eq!(f.params[0].ty, ApiType::Dict);
+    assert_eq!(f.since, 10);
+
+    assert_eq!(
+        info.function("nvim_call_atomic").unwrap().deprecated_since,
+        Some(12)
+    );
+    assert!(!info.function("nvim_buf_call").unwrap().is_rpc_callable());
+}
+
+#[test]
+fn handles_are_ext_values() {
+    let info = checked_in();
+    let buffer = info.handle("Buffer", 300).unwrap();
+    assert_eq!(buffer, Handle { ext: 0, id: 300 });
+    // the id is a msgpack uint 16
+    let value = Value::from(buffer);
+    assert_eq!(value, Value::Ext(0, vec![0xcd, 0x01, 0x2c]));
+
+    let Value::Ext(ext, data) = value else {
+        unreachable!()
+    };
+    assert_eq!(Handle::decode(ext, &data), Some(buffer));
+    // a negative fixint
+    assert_eq!(Handle::decode(1, &[0xff]), Some(Handle { ext: 1, id: -1 }));
+    assert_eq!(Handle::decode(2, &[0xa1, b'x']), None);
+    assert_eq!(Handle::decode(2, &[]), None);
+    assert_eq!(info.handle("Namespace", 1), None);
+}
+
+#[test]
+#[ignore = "needs nvim"]
+fn checked_in_api_info_matches_neovim() {
+    let bin = std::env::var("NVIM").unwrap_or_else(|_| "nvim".into());
+    let output = Command::new(&bin)
+        .arg("--api-info")
+        .output()
+        .unwrap_or_else(|e| {
+            panic!("Couldn't run {bin} --api-info, NVIM can point at neovim's binary: {e}")
+        });
+    let nvim = ApiInfo::read(output.stdout.as_slice()).unwrap();
+    let info = checked_in();
+
+    // update.lua keeps neovim's metadata as it is, save for the functions it drops
+    assert_eq!(
+        info.api_level, nvim.api_level,
+        "{bin} isn't the neovim the file was generated with"
+    );
+    assert_eq!(info.types, nvim.types);
+    for f in &info.functions {
+        let Some(current) = nvim.function(&f.name) else {
+            panic!("{} isn't in neovim's API anymore", f.name);
+        };
+        assert_eq!(f, current);
+    }
+}
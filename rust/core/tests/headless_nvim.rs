@@ -0,0 +1,213 @@
+//! Drives a real `nvim --embed --clean --headless` through `NeovimProcess` and the redraw
+//! decoder, checking what vimdow would draw without godot. The tests need neovim, so they
+//! only run with `cargo test -- --ignored`. `NVIM` can point at a binary that isn't on the path
+
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use rmpv::Value;
+use vimdow_core::grid::Ui;
+use vimdow_core::process::NeovimProcess;
+use vimdow_core::redraw::{RedrawEvent, parse_redraw};
+use vimdow_core::rpc::Message;
+
+const WIDTH: u64 = 40;
+const HEIGHT: u64 = 10;
+const TIMEOUT: Duration = Duration::from_secs(5);
+const ARGS: &[&str] = &["--embed", "--clean", "--headless"];
+
+fn nvim_bin() -> String {
+    let bin = std::env::var("NVIM").unwrap_or_else(|_| "nvim".into());
+    match Command::new(&bin).arg("--version").output() {
+        Ok(version) if version.status.success() => bin,
+        _ => panic!("Couldn't run {bin} --version, NVIM can point at neovim's binary"),
+    }
+}
+
+struct Headless {
+    np: NeovimProcess,
+    ui: Ui,
+}
+
+impl Headless {
+    fn start() -> Self {
+        let np = NeovimProcess::new(&nvim_bin(), ARGS).expect("Couldn't start neovim");
+        Self::attach(np)
+    }
+
+    fn attach(np: NeovimProcess) -> Self {
+        let mut nvim = Self {
+            np,
+            ui: Ui::default(),
+        };
+        nvim.np.request(
+            "nvim_ui_attach",
+            vec![
+                WIDTH.into(),
+                HEIGHT.into(),
+                Value::Map(vec![
+                    ("ext_linegrid".into(), true.into()),
+                    ("rgb".into(), true.into()),
+                ]),
+            ],
+        );
+        nvim.wait_until("the first screen", |ui| ui.flushes() > 0);
+        nvim
+    }
+
+    // applies redraw events until neovim flushes, false if it didn't in time
+    fn flush(&mut self) -> bool {
+        let deadline = Instant::now() + TIMEOUT;
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            let Some(message) = self.np.wait(left) else {
+                return false;
+            };
+            let Message::Notification { method, params } = message else {
+                continue;
+            };
+            if method != "redraw" {
+                continue;
+            }
+
+            let mut flushed = false;
+            for event in parse_redraw(&params) {
+                flushed |= event == RedrawEvent::Flush;
+                self.ui.apply(&event);
+            }
+            if flushed {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Applies flushes until `check` passes, failing with the screen if it never does
+    fn wait_until(&mut self, what: &str, check: impl Fn(&Ui) -> bool) {
+        let deadline = Instant::now() + TIMEOUT;
+        while !check(&self.ui) {
+            assert!(
+                Instant::now() < deadline && self.flush(),
+                "timed out waiting for {what}, the screen is:\n{}",
+                self.screen()
+            );
+        }
+    }
+
+    fn input(&mut self, keys: &str) {
+        self.np.request("nvim_input", vec![keys.into()]);
+    }
+
+    fn command(&mut self, command: &str) {
+        self.np.request("nvim_command", vec![command.into()]);
+    }
+
+    fn screen(&self) -> String {
+        let Some(grid) = self.ui.grid(1) else {
+            return String::new();
+        };
+        (0..grid.height())
+            .map(|row| grid.row_text(row))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn row(ui: &Ui, row: usize) -> String {
+    ui.grid(1).map(|g| g.row_text(row)).unwrap_or_default()
+}
+
+#[test]
+#[ignore = "needs nvim"]
+fn attaching_draws_an_empty_buffer() {
+    let mut nvim = Headless::start();
+
+    nvim.wait_until("the end of buffer lines", |ui| row(ui, 1).starts_with('~'));
+    let grid = nvim.ui.grid(1).unwrap();
+    assert_eq!(grid.width(), WIDTH as usize);
+    assert_eq!(grid.height(), HEIGHT as usize);
+    assert_eq!(nvim.ui.cursor(), (1, 0, 0));
+}
+
+#[test]
+#[ignore = "needs nvim"]
+fn typed_text_moves_the_cursor() {
+    let mut nvim = Headless::start();
+
+    nvim.input("ihello");
+    nvim.wait_until("the typed text", |ui| row(ui, 0).starts_with("hello"));
+    nvim.wait_until("the cursor after the text", |ui| ui.cursor() == (1, 0, 5));
+
+    // leaving insert mode moves back onto the last character
+    nvim.input("<Esc>");
+    nvim.wait_until("the cursor on the last character", |ui| {
+        ui.cursor() == (1, 0, 4)
+    });
+}
+
+#[test]
+#[ignore = "needs nvim"]
+fn highlights_are_defined_for_their_cells() {
+    let mut nvim = Headless::start();
+
+    nvim.command("highlight VimdowTest guifg=#ff0000 gui=bold");
+    nvim.input("iplain red<Esc>");
+    nvim.command("call matchadd('VimdowTest', 'red')");
+
+    nvim.wait_until("the highlighted word", |ui| {
+        let Some(cell) = ui.grid(1).and_then(|g| g.cell(0, 6)) else {
+            return false;
+        };
+        ui.hl_attr_value(cell.hl_id, "foreground")
+            .and_then(Value::as_u64)
+            == Some(0xff0000)
+    });
+
+    let grid = nvim.ui.grid(1).unwrap();
+    let plain = grid.cell(0, 0).unwrap();
+    let red = grid.cell(0, 6).unwrap();
+    assert_eq!(red.text, "r");
+    assert_ne!(plain.hl_id, red.hl_id);
+    assert_eq!(
+        nvim.ui.hl_attr_value(red.hl_id, "bold"),
+        Some(&Value::Boolean(true))
+    );
+}
+
+#[test]
+#[ignore = "needs nvim"]
+fn scrolling_keeps_the_grid_in_sync() {
+    let mut nvim = Headless::start();
+
+    nvim.command("call setline(1, map(range(1, 30), 'string(v:val)'))");
+    nvim.wait_until("the first line", |ui| row(ui, 0).trim_end() == "1");
+
+    nvim.input("G");
+    nvim.wait_until("the last line", |ui| {
+        (0..HEIGHT as usize).any(|r| row(ui, r).trim_end() == "30")
+    });
+    assert_ne!(row(&nvim.ui, 0).trim_end(), "1");
+
+    nvim.input("gg");
+    nvim.wait_until("the first line again", |ui| row(ui, 0).trim_end() == "1");
+    assert_eq!(row(&nvim.ui, 1).trim_end(), "2");
+}
+
+#[test]
+#[ignore = "needs nvim"]
+fn recorded_sessions_replay_the_same_screen() {
+    let bin = nvim_bin();
+    let path = std::env::temp_dir().join(format!("vimdow-{}-headless.msgpack", std::process::id()));
+
+    let np = NeovimProcess::new_recorded(&bin, ARGS, &path).expect("Couldn't start neovim");
+    let mut nvim = Headless::attach(np);
+    nvim.input("irecorded<Esc>");
+    nvim.wait_until("the typed text", |ui| row(ui, 0).starts_with("recorded"));
+    let screen = nvim.screen();
+    let flushes = nvim.ui.flushes();
+    drop(nvim);
+
+    let mut replay = Headless::attach(NeovimProcess::replay(&path).unwrap());
+    replay.wait_until("every recorded flush", |ui| ui.flushes() == flushes);
+    assert_eq!(replay.screen(), screen);
+    std::fs::remove_file(path).unwrap();
+}
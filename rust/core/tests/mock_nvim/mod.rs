@@ -0,0 +1,356 @@
+//! A fake neovim speaking msgpack-rpc over an in-memory pipe. Tests drive it from their own
+//! thread: `serve` answers the next request the way neovim would, and the `send_*` methods
+//! and `exit` inject what a broken, slow or crashing neovim could do
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use rmpv::Value;
+use vimdow_core::api_info::Handle;
+use vimdow_core::process::NeovimProcess;
+use vimdow_core::rpc::Message;
+
+// how long the fake waits for the client before failing the test
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The ext types of its api-info, with other ids than a real neovim's so that
+/// clients assuming those get caught
+pub const EXT_TYPES: [(&str, i8); 3] = [("Buffer", 5), ("Window", 6), ("Tabpage", 7)];
+
+// the handle of its only buffer
+const BUFFER: Handle = Handle { ext: 5, id: 1 };
+
+pub struct PipeWriter(mpsc::Sender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct PipeReader {
+    rx: mpsc::Receiver<Vec<u8>>,
+    buf: VecDeque<u8>,
+    // the client's reader blocks like it would on stdout
+    timeout: Option<Duration>,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.buf.is_empty() {
+            let chunk = match self.timeout {
+                Some(timeout) => self.rx.recv_timeout(timeout).map_err(|e| match e {
+                    mpsc::RecvTimeoutError::Timeout => io::Error::from(io::ErrorKind::TimedOut),
+                    mpsc::RecvTimeoutError::Disconnected => {
+                        io::Error::from(io::ErrorKind::UnexpectedEof)
+                    }
+                }),
+                None => self
+                    .rx
+                    .recv()
+                    .map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof)),
+            };
+            match chunk {
+                Ok(chunk) => self.buf.extend(chunk),
+                // the other end closed
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(0),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let n = out.len().min(self.buf.len());
+        for (o, b) in out.iter_mut().zip(self.buf.drain(..n)) {
+            *o = b;
+        }
+        Ok(n)
+    }
+}
+
+fn pipe(timeout: Option<Duration>) -> (PipeReader, PipeWriter) {
+    let (tx, rx) = mpsc::channel();
+    (
+        PipeReader {
+            rx,
+            buf: VecDeque::new(),
+            timeout,
+        },
+        PipeWriter(tx),
+    )
+}
+
+pub fn encode(v: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    rmpv::encode::write_value(&mut buf, v).unwrap();
+    buf
+}
+
+pub struct MockNeovim {
+    // what the client sent, and the way back to it. Both close on `exit`
+    from_client: Option<PipeReader>,
+    to_client: Option<PipeWriter>,
+
+    width: usize,
+    height: usize,
+    lines: Vec<String>,
+    // row and column
+    cursor: (usize, usize),
+    attached: bool,
+}
+
+/// A client connected to a fresh fake neovim with one empty line
+pub fn start() -> (NeovimProcess, MockNeovim) {
+    let (from_client, client_writer) = pipe(Some(TIMEOUT));
+    let (client_reader, to_client) = pipe(None);
+    let np = NeovimProcess::connect(client_reader, client_writer);
+    let nvim = MockNeovim {
+        from_client: Some(from_client),
+        to_client: Some(to_client),
+        width: 0,
+        height: 0,
+        lines: vec![String::new()],
+        cursor: (0, 0),
+        attached: false,
+    };
+    (np, nvim)
+}
+
+fn api_info() -> Value {
+    let types = EXT_TYPES
+        .iter()
+        .map(|&(name, id)| (name.into(), Value::Map(vec![("id".into(), id.into())])))
+        .collect();
+    Value::Map(vec![
+        (
+            "version".into(),
+            Value::Map(vec![("api_level".into(), 13.into())]),
+        ),
+        ("types".into(), Value::Map(types)),
+        ("functions".into(), Value::Array(vec![])),
+    ])
+}
+
+// checks a Buffer argument, which is its handle or 0 for the current buffer
+fn buffer(method: &str, v: Option<&Value>) -> Result<(), String> {
+    let valid = match v {
+        Some(Value::Integer(i)) => matches!(i.as_i64(), Some(0 | 1)),
+        Some(Value::Ext(ext, data)) => Handle::decode(*ext, data) == Some(BUFFER),
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Wrong type for argument 1 when calling {method}, expecting Buffer"
+        ))
+    }
+}
+
+fn redraw_event(name: &str, calls: Vec<Vec<Value>>) -> Value {
+    let mut event = vec![name.into()];
+    event.extend(calls.into_iter().map(Value::Array));
+    Value::Array(event)
+}
+
+impl MockNeovim {
+    /// The next message the client sent, if it sent one in time
+    pub fn try_next(&mut self, timeout: Duration) -> Option<Message> {
+        let reader = self.from_client.as_mut()?;
+        let saved = reader.timeout.replace(timeout);
+        let v = rmpv::decode::read_value(reader);
+        reader.timeout = saved;
+        Message::parse(v.ok()?).ok()
+    }
+
+    pub fn next(&mut self) -> Message {
+        self.try_next(TIMEOUT)
+            .expect("the client didn't send anything")
+    }
+
+    pub fn send_bytes(&mut self, bytes: &[u8]) {
+        if let Some(to_client) = self.to_client.as_mut() {
+            to_client.write_all(bytes).unwrap();
+        }
+    }
+
+    pub fn send_value(&mut self, v: &Value) {
+        self.send_bytes(&encode(v));
+    }
+
+    pub fn send(&mut self, message: Message) {
+        self.send_value(&message.into());
+    }
+
+    /// Closes both ends like neovim exiting would
+    pub fn exit(&mut self) {
+        self.from_client = None;
+        self.to_client = None;
+    }
+
+    pub fn line(&self, row: usize) -> &str {
+        &self.lines[row]
+    }
+
+    /// Answers the next request the client makes, returning its method
+    pub fn serve(&mut self) -> String {
+        let Message::Request {
+            msgid,
+            method,
+            params,
+        } = self.next()
+        else {
+            panic!("expected a request from the client");
+        };
+
+        let reply = self.call(&method, &params);
+        let (error, result) = match reply {
+            Ok(result) => (Value::Nil, result),
+            // neovim's errors are [type, message]
+            Err(message) => (Value::Array(vec![0.into(), message.into()]), Value::Nil),
+        };
+        self.send(Message::Response {
+            msgid,
+            error,
+            result,
+        });
+        if self.attached {
+            self.redraw();
+        }
+        method
+    }
+
+    fn call(&mut self, method: &str, params: &[Value]) -> Result<Value, String> {
+        let size = |params: &[Value]| match params {
+            [w, h, ..] => Some((w.as_u64()? as usize, h.as_u64()? as usize)),
+            _ => None,
+        };
+        match method {
+            "nvim_ui_attach" => {
+                (self.width, self.height) = size(params).ok_or("Invalid size")?;
+                self.attached = true;
+                Ok(Value::Nil)
+            }
+            "nvim_ui_try_resize" => {
+                (self.width, self.height) = size(params).ok_or("Invalid size")?;
+                Ok(Value::Nil)
+            }
+            "nvim_input" => {
+                let keys = params
+                    .first()
+                    .and_then(Value::as_str)
+                    .ok_or("Expected a string")?;
+                self.input(keys);
+                Ok(keys.len().into())
+            }
+            "nvim_get_current_line" => Ok(self.lines[self.cursor.0].as_str().into()),
+            "nvim_get_api_info" => Ok(Value::Array(vec![1.into(), api_info()])),
+            "nvim_get_current_buf" => Ok(BUFFER.into()),
+            "nvim_buf_line_count" => {
+                buffer(method, params.first())?;
+                Ok(self.lines.len().into())
+            }
+            "nvim_command" => Ok(Value::Nil),
+            "nvim_call_atomic" => {
+                let calls = params
+                    .first()
+                    .and_then(Value::as_array)
+                    .ok_or("Expected an array")?;
+                let mut results = vec![];
+                for (i, call) in calls.iter().enumerate() {
+                    let [method, params] = call.as_array().map(Vec::as_slice).unwrap_or_default()
+                    else {
+                        return Err("Items in calls array must be arrays of size 2".into());
+                    };
+                    let method = method.as_str().unwrap_or_default();
+                    let params = params.as_array().map(Vec::as_slice).unwrap_or_default();
+                    match self.call(method, params) {
+                        Ok(result) => results.push(result),
+                        Err(message) => {
+                            let error = vec![i.into(), 0.into(), message.into()];
+                            return Ok(Value::Array(vec![
+                                Value::Array(results),
+                                Value::Array(error),
+                            ]));
+                        }
+                    }
+                }
+                Ok(Value::Array(vec![Value::Array(results), Value::Nil]))
+            }
+            _ => Err(format!("Invalid method: {method}")),
+        }
+    }
+
+    // types like insert mode, only knowing <CR> and <lt>
+    fn input(&mut self, keys: &str) {
+        let mut rest = keys;
+        while let Some(c) = rest.chars().next() {
+            if c == '<'
+                && let Some(end) = rest.find('>')
+            {
+                match &rest[1..end] {
+                    "CR" => {
+                        self.lines.insert(self.cursor.0 + 1, String::new());
+                        self.cursor = (self.cursor.0 + 1, 0);
+                    }
+                    "lt" => self.type_char('<'),
+                    _ => {}
+                }
+                rest = &rest[end + 1..];
+                continue;
+            }
+            self.type_char(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    fn type_char(&mut self, c: char) {
+        let (row, col) = self.cursor;
+        self.lines[row].insert(col, c);
+        self.cursor.1 += c.len_utf8();
+    }
+
+    /// Sends the whole screen, like the first redraw after attaching
+    pub fn redraw(&mut self) {
+        let mut events = vec![
+            redraw_event(
+                "grid_resize",
+                vec![vec![1.into(), self.width.into(), self.height.into()]],
+            ),
+            redraw_event("grid_clear", vec![vec![1.into()]]),
+        ];
+        let lines = (0..self.height)
+            .map(|row| {
+                let text = self.lines.get(row).map(String::as_str).unwrap_or("~");
+                let mut cells: Vec<Value> = text
+                    .chars()
+                    .take(self.width)
+                    .map(|c| Value::Array(vec![c.to_string().into(), 0.into()]))
+                    .collect();
+                let fill = self.width.saturating_sub(cells.len());
+                if fill > 0 {
+                    cells.push(Value::Array(vec![" ".into(), 0.into(), fill.into()]));
+                }
+                vec![1.into(), row.into(), 0.into(), Value::Array(cells)]
+            })
+            .collect();
+        events.push(redraw_event("grid_line", lines));
+        events.push(redraw_event(
+            "grid_cursor_goto",
+            vec![vec![1.into(), self.cursor.0.into(), self.cursor.1.into()]],
+        ));
+        events.push(redraw_event("flush", vec![vec![]]));
+
+        self.send(Message::Notification {
+            method: "redraw".into(),
+            params: events,
+        });
+    }
+}
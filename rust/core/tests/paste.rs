@@ -0,0 +1,44 @@
+//! Chunks of large pastes, checked with a small chunk size
+
+use vimdow_core::paste::paste_chunks;
+
+fn chunks(text: &str) -> Vec<&str> {
+    let chunks = paste_chunks(text, 8);
+    assert_eq!(chunks.concat(), text);
+    assert!(chunks.iter().all(|c| c.len() <= 8), "{chunks:?}");
+    chunks
+}
+
+#[test]
+fn short_text_is_one_chunk() {
+    assert_eq!(chunks("let x\n"), ["let x\n"]);
+    assert_eq!(chunks(""), [""]);
+}
+
+#[test]
+fn breaks_after_newlines() {
+    assert_eq!(chunks("ab\ncd\nefghij"), ["ab\ncd\n", "efghij"]);
+}
+
+#[test]
+fn text_without_newlines_is_cut_at_the_limit() {
+    assert_eq!(
+        chunks("abcdefghijklmnopqrst"),
+        ["abcdefgh", "ijklmnop", "qrst"]
+    );
+}
+
+#[test]
+fn crlf_straddling_the_limit_stays_together() {
+    assert_eq!(chunks("abcdefg\r\nhij"), ["abcdefg", "\r\nhij"]);
+    // the newline of a CRLF is the break when it fits
+    assert_eq!(chunks("abcdef\r\nghij"), ["abcdef\r\n", "ghij"]);
+}
+
+#[test]
+fn characters_at_the_limit_are_not_split() {
+    // "é" is bytes 7 and 8, "🦀" bytes 6 to 9
+    assert_eq!(chunks("abcdefgé"), ["abcdefg", "é"]);
+    assert_eq!(chunks("abcdef🦀xy"), ["abcdef", "🦀xy"]);
+    assert_eq!(chunks("日本語の文"), ["日本", "語の", "文"]);
+}
@@ -0,0 +1,236 @@
+//! Properties of the msgpack layer checked over random values. Every property runs over
+//! the fixed `SEEDS` and its seeds in `properties.regressions`, so runs are repeatable.
+//! `VIMDOW_SEED` tries one more seed, and a seed that fails belongs in the regressions file
+
+use rmpv::Value;
+use vimdow_core::redraw::parse_redraw;
+use vimdow_core::rpc::Message;
+use vimdow_core::session::read_entries;
+
+const CASES: usize = 2000;
+
+const SEEDS: &[u64] = &[1, 0x5eed, 0xdead_beef, 0x0123_4567_89ab_cdef];
+
+// lines of `<property> <seed>`, with `#` comments
+const REGRESSIONS: &str = include_str!("properties.regressions");
+
+// xorshift64*, good enough to pick values
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn bytes(&mut self, max: u64) -> Vec<u8> {
+        (0..self.below(max + 1))
+            .map(|_| self.next() as u8)
+            .collect()
+    }
+
+    fn string(&mut self) -> String {
+        const PIECES: &[&str] = &["a", "Z", " ", "<", "\n", "é", "日", "🦀", "\u{301}", ""];
+        (0..self.below(8))
+            .map(|_| PIECES[self.below(PIECES.len() as u64) as usize])
+            .collect()
+    }
+
+    fn integer(&mut self) -> Value {
+        match self.below(4) {
+            0 => self.below(300).into(),
+            1 => (self.next() as i64).into(),
+            2 => self.next().into(),
+            _ => (-(self.below(300) as i64)).into(),
+        }
+    }
+
+    /// Any value, nested up to `depth` deep
+    fn value(&mut self, depth: u32) -> Value {
+        let kinds = if depth == 0 { 8 } else { 10 };
+        match self.below(kinds) {
+            0 => Value::Nil,
+            1 => Value::Boolean(self.below(2) == 0),
+            2 => self.integer(),
+            3 => Value::F32(self.next() as u32 as f32 / 7.0),
+            4 => Value::F64(self.next() as f64 / 3.0),
+            5 => self.string().into(),
+            // strings neovim can send that aren't UTF-8
+            6 => {
+                let bytes = self.bytes(8);
+                let mut str8 = vec![0xd9, bytes.len() as u8];
+                str8.extend(bytes);
+                rmpv::decode::read_value(&mut str8.as_slice()).unwrap()
+            }
+            7 => match self.below(2) {
+                0 => Value::Binary(self.bytes(16)),
+                _ => Value::Ext(self.next() as i8, self.bytes(9)),
+            },
+            8 => Value::Array((0..self.below(5)).map(|_| self.value(depth - 1)).collect()),
+            _ => Value::Map(
+                (0..self.below(4))
+                    .map(|_| (self.value(depth - 1), self.value(depth - 1)))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn message(&mut self) -> Message {
+        let params = (0..self.below(4)).map(|_| self.value(2)).collect();
+        match self.below(3) {
+            0 => Message::Request {
+                msgid: self.next() as u32,
+                method: self.string(),
+                params,
+            },
+            1 => Message::Response {
+                msgid: self.next() as u32,
+                error: self.value(1),
+                result: self.value(2),
+            },
+            _ => Message::Notification {
+                method: self.string(),
+                params,
+            },
+        }
+    }
+
+    // a value that's nearly a redraw event, so parsing gets past the first checks
+    fn redraw_batch(&mut self) -> Value {
+        const EVENTS: &[&str] = &[
+            "grid_resize",
+            "grid_clear",
+            "grid_destroy",
+            "grid_cursor_goto",
+            "grid_line",
+            "grid_scroll",
+            "hl_attr_define",
+            "default_colors_set",
+            "flush",
+            "mode_change",
+        ];
+        let mut batch = vec![EVENTS[self.below(EVENTS.len() as u64) as usize].into()];
+        for _ in 0..self.below(3) {
+            let args = (0..self.below(7))
+                .map(|_| match self.below(3) {
+                    0 => self.integer(),
+                    1 => Value::Array(
+                        (0..self.below(4))
+                            .map(|_| {
+                                Value::Array((0..self.below(4)).map(|_| self.value(0)).collect())
+                            })
+                            .collect(),
+                    ),
+                    _ => self.value(2),
+                })
+                .collect();
+            batch.push(Value::Array(args));
+        }
+        Value::Array(batch)
+    }
+}
+
+fn encode(v: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    rmpv::encode::write_value(&mut buf, v).unwrap();
+    buf
+}
+
+fn seeds(name: &str) -> Vec<u64> {
+    let mut seeds = SEEDS.to_vec();
+    for line in REGRESSIONS.lines() {
+        let line = line.split('#').next().unwrap().trim();
+        if let Some((property, seed)) = line.split_once(' ')
+            && property == name
+        {
+            seeds.push(seed.trim().parse().expect("seeds must be numbers"));
+        }
+    }
+    if let Ok(seed) = std::env::var("VIMDOW_SEED") {
+        seeds.push(seed.parse().expect("VIMDOW_SEED must be a number"));
+    }
+    seeds
+}
+
+// runs a property over CASES values of each seed, naming the seed that fails
+fn check(name: &str, property: impl Fn(&mut Rng)) {
+    for seed in seeds(name) {
+        let mut rng = Rng(seed | 1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            for _ in 0..CASES {
+                property(&mut rng);
+            }
+        }));
+        if let Err(e) = result {
+            eprintln!("{name} failed, add `{name} {seed}` to tests/properties.regressions");
+            std::panic::resume_unwind(e);
+        }
+    }
+}
+
+#[test]
+fn messages_survive_encoding() {
+    check("messages_survive_encoding", |rng| {
+        let message = rng.message();
+        let bytes = encode(&message.clone().into());
+        let decoded = rmpv::decode::read_value(&mut bytes.as_slice()).unwrap();
+        let parsed = Message::parse(decoded).unwrap();
+        assert_eq!(encode(&parsed.into()), bytes, "{message:?}");
+    });
+}
+
+#[test]
+fn any_value_is_classified_without_panicking() {
+    check("any_value_is_classified_without_panicking", |rng| {
+        let v = match rng.below(2) {
+            0 => rng.value(3),
+            // close to a message, with one field likely wrong
+            _ => Value::Array((0..rng.below(6)).map(|_| rng.value(1)).collect()),
+        };
+        if let Ok(message) = Message::parse(v.clone()) {
+            assert_eq!(encode(&message.into()), encode(&v));
+        }
+    });
+}
+
+#[test]
+fn any_bytes_are_read_without_panicking() {
+    check("any_bytes_are_read_without_panicking", |rng| {
+        // a valid message, damaged at random
+        let mut bytes = encode(&rng.message().into());
+        for _ in 0..rng.below(4) {
+            let i = rng.below(bytes.len() as u64) as usize;
+            bytes[i] = rng.next() as u8;
+        }
+        bytes.truncate(rng.below(bytes.len() as u64 + 1) as usize);
+
+        let mut stream = bytes.as_slice();
+        while let Ok(v) = rmpv::decode::read_value(&mut stream) {
+            let _ = Message::parse(v);
+        }
+        if let Ok(entries) = read_entries(bytes.as_slice()) {
+            for entry in entries {
+                let _ = entry.value();
+            }
+        }
+    });
+}
+
+#[test]
+fn any_redraw_is_parsed_without_panicking() {
+    check("any_redraw_is_parsed_without_panicking", |rng| {
+        let params: Vec<Value> = (0..rng.below(4))
+            .map(|_| match rng.below(4) {
+                0 => rng.value(3),
+                _ => rng.redraw_batch(),
+            })
+            .collect();
+        parse_redraw(&params);
+    });
+}
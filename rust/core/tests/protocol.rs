@@ -0,0 +1,426 @@
+//! Drives `NeovimProcess` against the fake neovim in `mock_nvim`, including the broken
+//! streams a real neovim can't be made to send
+
+mod mock_nvim;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rmpv::Value;
+use vimdow_core::api_info::{ApiInfo, Handle};
+use vimdow_core::batch::{Batch, BatchError, BatchResult};
+use vimdow_core::err::VimdowError;
+use vimdow_core::grid::Ui;
+use vimdow_core::process::NeovimProcess;
+use vimdow_core::redraw::parse_redraw;
+use vimdow_core::requests::{Route, route};
+use vimdow_core::rpc::Message;
+
+use mock_nvim::{MockNeovim, TIMEOUT, encode};
+
+const SHORT: Duration = Duration::from_millis(50);
+
+fn attach(np: &mut NeovimProcess, nvim: &mut MockNeovim, ui: &mut Ui) {
+    let msgid = np.request(
+        "nvim_ui_attach",
+        vec![
+            20.into(),
+            3.into(),
+            Value::Map(vec![("ext_linegrid".into(), true.into())]),
+        ],
+    );
+    assert_eq!(nvim.serve(), "nvim_ui_attach");
+    let response = np.wait(TIMEOUT);
+    assert!(
+        matches!(response, Some(Message::Response { msgid: id, error: Value::Nil, .. }) if id as i32 == msgid),
+        "{response:?}"
+    );
+    redraw(np, ui);
+}
+
+// applies the next message, which has to be a redraw
+fn redraw(np: &mut NeovimProcess, ui: &mut Ui) {
+    match np.wait(TIMEOUT) {
+        Some(Message::Notification { method, params }) if method == "redraw" => {
+            for event in parse_redraw(&params) {
+                ui.apply(&event);
+            }
+        }
+        other => panic!("expected a redraw, got {other:?}"),
+    }
+}
+
+fn row(ui: &Ui, row: usize) -> String {
+    ui.grid(1).unwrap().row_text(row).trim_end().to_string()
+}
+
+#[test]
+fn attaching_draws_the_grid() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    let mut ui = Ui::default();
+    attach(&mut np, &mut nvim, &mut ui);
+
+    let grid = ui.grid(1).unwrap();
+    assert_eq!((grid.width(), grid.height()), (20, 3));
+    assert_eq!(row(&ui, 0), "");
+    assert_eq!(row(&ui, 1), "~");
+    assert_eq!(ui.flushes(), 1);
+}
+
+#[test]
+fn input_is_typed_and_drawn() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    let mut ui = Ui::default();
+    attach(&mut np, &mut nvim, &mut ui);
+
+    np.request("nvim_input", vec!["a<lt>b<CR>c".into()]);
+    assert_eq!(nvim.serve(), "nvim_input");
+    let Some(Message::Response { result, .. }) = np.wait(TIMEOUT) else {
+        panic!("nvim_input wasn't answered");
+    };
+    // neovim answers with the bytes it took
+    assert_eq!(result.as_u64(), Some(11));
+    redraw(&mut np, &mut ui);
+
+    assert_eq!(row(&ui, 0), "a<b");
+    assert_eq!(row(&ui, 1), "c");
+    assert_eq!(ui.cursor(), (1, 1, 1));
+    assert_eq!(nvim.line(0), "a<b");
+}
+
+#[test]
+fn api_calls_are_answered() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    np.request("nvim_input", vec!["hello".into()]);
+    nvim.serve();
+    np.wait(TIMEOUT).unwrap();
+
+    let msgid = np.request("nvim_get_current_line", vec![]);
+    nvim.serve();
+    assert_eq!(
+        np.wait(TIMEOUT),
+        Some(Message::Response {
+            msgid: msgid as u32,
+            error: Value::Nil,
+            result: "hello".into(),
+        })
+    );
+
+    np.request("nvim_not_a_method", vec![]);
+    nvim.serve();
+    let Some(Message::Response { error, result, .. }) = np.wait(TIMEOUT) else {
+        panic!("the unknown method wasn't answered");
+    };
+    assert_eq!(result, Value::Nil);
+    assert_eq!(
+        error.as_array().and_then(|e| e[1].as_str()),
+        Some("Invalid method: nvim_not_a_method")
+    );
+}
+
+#[test]
+fn handles_use_the_ext_types_neovim_sent() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    np.request("nvim_get_api_info", vec![]);
+    nvim.serve();
+    let Some(Message::Response { result, .. }) = np.wait(TIMEOUT) else {
+        panic!("nvim_get_api_info wasn't answered");
+    };
+    // [channel id, metadata]
+    let info = ApiInfo::parse(&result.as_array().unwrap()[1]).unwrap();
+    let ids: Vec<_> = info.types.iter().map(|t| (t.name.as_str(), t.id)).collect();
+    assert_eq!(ids, mock_nvim::EXT_TYPES);
+
+    np.request("nvim_get_current_buf", vec![]);
+    nvim.serve();
+    let Some(Message::Response {
+        result: Value::Ext(ext, data),
+        ..
+    }) = np.wait(TIMEOUT)
+    else {
+        panic!("nvim_get_current_buf wasn't answered with a handle");
+    };
+    let buffer = Handle::decode(ext, &data).unwrap();
+    assert_eq!(Some(buffer), info.handle("Buffer", 1));
+
+    // sent back the way it came
+    np.request("nvim_buf_line_count", vec![buffer.into()]);
+    nvim.serve();
+    let Some(Message::Response { error, result, .. }) = np.wait(TIMEOUT) else {
+        panic!("nvim_buf_line_count wasn't answered");
+    };
+    assert_eq!((error, result), (Value::Nil, 1.into()));
+
+    // the ids of the checked-in api-info aren't this neovim's
+    let window = info.handle("Window", 1).unwrap();
+    np.request("nvim_buf_line_count", vec![window.into()]);
+    nvim.serve();
+    let Some(Message::Response { error, .. }) = np.wait(TIMEOUT) else {
+        panic!("nvim_buf_line_count wasn't answered");
+    };
+    assert!(!error.is_nil());
+}
+
+#[test]
+fn batches_are_one_round_trip() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    let mut batch = Batch::new();
+    batch
+        .add("nvim_input", vec!["one<CR>two".into()])
+        .add("nvim_command", vec!["write".into()])
+        .add("nvim_buf_line_count", vec![0.into()]);
+    let msgid = np.batch(batch);
+    assert_eq!(nvim.serve(), "nvim_call_atomic");
+
+    let Some(Message::Response {
+        msgid: id,
+        error: Value::Nil,
+        result,
+    }) = np.wait(TIMEOUT)
+    else {
+        panic!("the batch wasn't answered");
+    };
+    assert_eq!(id as i32, msgid);
+    assert_eq!(
+        BatchResult::parse(&result).unwrap(),
+        BatchResult {
+            results: vec![10.into(), Value::Nil, 2.into()],
+            error: None,
+        }
+    );
+    assert_eq!(nvim.line(1), "two");
+}
+
+#[test]
+fn batches_stop_at_the_failing_call() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    let mut batch = Batch::new();
+    batch
+        .add("nvim_input", vec!["a".into()])
+        .add("nvim_not_a_method", vec![])
+        .add("nvim_input", vec!["b".into()]);
+    np.batch(batch);
+    nvim.serve();
+
+    let Some(Message::Response { result, .. }) = np.wait(TIMEOUT) else {
+        panic!("the batch wasn't answered");
+    };
+    let result = BatchResult::parse(&result).unwrap();
+    assert_eq!(result.results, vec![Value::from(1)]);
+    assert_eq!(
+        result.error,
+        Some(BatchError {
+            index: 1,
+            kind: 0,
+            message: "Invalid method: nvim_not_a_method".into(),
+        })
+    );
+    // nothing after the failing call runs
+    assert_eq!(nvim.line(0), "a");
+}
+
+#[test]
+fn requests_from_neovim_are_answered_once() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    nvim.send(Message::Request {
+        msgid: 7,
+        method: "vimdow_ping".into(),
+        params: vec![],
+    });
+    let Some(Message::Request { msgid, method, .. }) = np.wait(TIMEOUT) else {
+        panic!("the request didn't arrive");
+    };
+    assert_eq!((msgid, method.as_str()), (7, "vimdow_ping"));
+
+    np.respond(7, Value::Nil, "pong".into()).unwrap();
+    assert!(matches!(
+        np.respond(7, Value::Nil, "pong".into()),
+        Err(VimdowError::UnknownRequest(7))
+    ));
+    assert!(matches!(
+        np.respond(8, Value::Nil, Value::Nil),
+        Err(VimdowError::UnknownRequest(8))
+    ));
+
+    assert_eq!(
+        nvim.next(),
+        Message::Response {
+            msgid: 7,
+            error: Value::Nil,
+            result: "pong".into(),
+        }
+    );
+    assert_eq!(nvim.try_next(SHORT), None);
+}
+
+#[test]
+fn unhandled_requests_are_rejected() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    nvim.send(Message::Request {
+        msgid: 3,
+        method: "vimdow_nothing".into(),
+        params: vec![],
+    });
+    let Some(Message::Request { msgid, method, .. }) = np.wait(TIMEOUT) else {
+        panic!("the request didn't arrive");
+    };
+    let handlers = HashMap::from([("vimdow_goto".to_string(), ())]);
+    assert_eq!(route(&method, &handlers, false), Route::Reject);
+    np.reject(msgid, &method).unwrap();
+
+    assert_eq!(
+        nvim.next(),
+        Message::Response {
+            msgid: 3,
+            error: "Unknown method: vimdow_nothing".into(),
+            result: Value::Nil,
+        }
+    );
+}
+
+#[test]
+fn requests_are_routed() {
+    let handlers = HashMap::from([
+        ("vimdow_goto".to_string(), ()),
+        // the clipboard goes first
+        ("vimdow_clipboard_get".to_string(), ()),
+    ]);
+    for listeners in [false, true] {
+        assert_eq!(route("vimdow_goto", &handlers, listeners), Route::Handler);
+        assert_eq!(
+            route("vimdow_clipboard_set", &handlers, listeners),
+            Route::Clipboard
+        );
+        assert_eq!(
+            route("vimdow_clipboard_get", &handlers, listeners),
+            Route::Clipboard
+        );
+    }
+    assert_eq!(route("vimdow_nothing", &handlers, true), Route::Listeners);
+    assert_eq!(route("vimdow_nothing", &handlers, false), Route::Reject);
+}
+
+#[test]
+fn unrequested_responses_are_passed_on() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    let response = Message::Response {
+        msgid: 42,
+        error: Value::Nil,
+        result: Value::Nil,
+    };
+    nvim.send(response.clone());
+    assert_eq!(np.wait(TIMEOUT), Some(response));
+    assert!(np.is_running());
+}
+
+#[test]
+fn malformed_messages_are_skipped() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    for v in [
+        Value::from("not rpc"),
+        Value::Array(vec![2.into()]),
+        Value::Array(vec![7.into(), 1.into(), 2.into()]),
+        Value::Array(vec![
+            0.into(),
+            (-1).into(),
+            "m".into(),
+            Value::Array(vec![]),
+        ]),
+        Value::Array(vec![0.into(), 1.into(), "m".into(), "not params".into()]),
+        Value::Array(vec![1.into(), "id".into(), Value::Nil, Value::Nil]),
+        Value::Array(vec![2.into(), 5.into(), Value::Array(vec![])]),
+        Value::Array(vec![
+            2.into(),
+            Value::Binary(vec![0xff]),
+            Value::Array(vec![]),
+        ]),
+    ] {
+        nvim.send_value(&v);
+    }
+    let valid = Message::Notification {
+        method: "after".into(),
+        params: vec![],
+    };
+    nvim.send(valid.clone());
+
+    assert_eq!(np.wait(TIMEOUT), Some(valid));
+    assert_eq!(np.check(), None);
+    assert!(np.is_running());
+}
+
+#[test]
+fn messages_split_across_writes_wait_for_the_rest() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    let message = Message::Notification {
+        method: "split".into(),
+        params: vec!["x".repeat(100).into()],
+    };
+    let bytes = encode(&message.clone().into());
+    let (first, rest) = bytes.split_at(bytes.len() / 2);
+
+    nvim.send_bytes(first);
+    assert_eq!(np.wait(SHORT), None);
+    nvim.send_bytes(rest);
+    assert_eq!(np.wait(TIMEOUT), Some(message));
+}
+
+#[test]
+fn slow_responses_are_waited_for() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    np.request("nvim_command", vec!["sleep 1".into()]);
+    assert_eq!(np.wait(SHORT), None);
+    assert!(np.is_running());
+
+    nvim.serve();
+    assert!(matches!(
+        np.wait(TIMEOUT),
+        Some(Message::Response { msgid: 0, .. })
+    ));
+}
+
+#[test]
+fn exiting_early_stops_the_client() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    nvim.send(Message::Notification {
+        method: "last words".into(),
+        params: vec![],
+    });
+    nvim.exit();
+
+    assert!(np.wait(TIMEOUT).is_some());
+    assert_eq!(np.wait(TIMEOUT), None);
+    assert!(!np.is_running());
+    // talking to a neovim that's gone isn't fatal
+    np.request("nvim_input", vec!["i".into()]);
+    assert_eq!(np.check(), None);
+}
+
+#[test]
+fn stray_bytes_are_skipped() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    // 0xc1 is never used by msgpack, and reads as nil
+    nvim.send_bytes(&[0xc1]);
+    let valid = Message::Notification {
+        method: "after".into(),
+        params: vec![],
+    };
+    nvim.send(valid.clone());
+    assert_eq!(np.wait(TIMEOUT), Some(valid));
+}
+
+#[test]
+fn exiting_mid_message_ends_the_stream() {
+    let (mut np, mut nvim) = mock_nvim::start();
+    let bytes = encode(
+        &Message::Notification {
+            method: "cut off".into(),
+            params: vec![],
+        }
+        .into(),
+    );
+    nvim.send_bytes(&bytes[..bytes.len() - 1]);
+    nvim.exit();
+
+    assert_eq!(np.wait(TIMEOUT), None);
+    assert!(!np.is_running());
+}
@@ -0,0 +1,99 @@
+//! Recording session files and replaying them through `NeovimProcess` without neovim
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rmpv::Value;
+use vimdow_core::grid::Ui;
+use vimdow_core::process::NeovimProcess;
+use vimdow_core::redraw::parse_redraw;
+use vimdow_core::rpc::Message;
+use vimdow_core::session::{Direction, Recorder, read_session};
+
+fn session_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("vimdow-{}-{name}.msgpack", std::process::id()))
+}
+
+fn encode(v: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    rmpv::encode::write_value(&mut buf, v).unwrap();
+    buf
+}
+
+fn redraw(events: Vec<Value>) -> Value {
+    Value::Array(vec![2.into(), "redraw".into(), Value::Array(events)])
+}
+
+fn event(name: &str, calls: Vec<Vec<Value>>) -> Value {
+    let mut event = vec![name.into()];
+    event.extend(calls.into_iter().map(Value::Array));
+    Value::Array(event)
+}
+
+#[test]
+fn replay_plays_back_what_neovim_sent() {
+    let path = session_path("replay");
+    let recorder = Recorder::create(&path).unwrap();
+    let attach = Value::Array(vec![
+        0.into(),
+        0.into(),
+        "nvim_ui_attach".into(),
+        Value::Array(vec![]),
+    ]);
+    recorder.record(Direction::ToNeovim, &encode(&attach));
+    let screen = redraw(vec![
+        event("grid_resize", vec![vec![1.into(), 5.into(), 1.into()]]),
+        event(
+            "grid_line",
+            vec![vec![
+                1.into(),
+                0.into(),
+                0.into(),
+                Value::Array(vec![Value::Array(vec!["h".into(), 0.into()])]),
+            ]],
+        ),
+        event("flush", vec![vec![]]),
+    ]);
+    recorder.record(Direction::FromNeovim, &encode(&screen));
+    drop(recorder);
+
+    let entries = read_session(&path).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].direction, Direction::ToNeovim);
+    assert_eq!(entries[1].direction, Direction::FromNeovim);
+    assert!(entries[0].time <= entries[1].time);
+    assert_eq!(entries[1].value().unwrap(), screen);
+
+    let mut np = NeovimProcess::replay(&path).unwrap();
+    // requests go nowhere, and the recorded response to them isn't expected
+    np.request("nvim_input", vec!["i".into()]);
+    let Some(message) = np.wait(Duration::from_secs(5)) else {
+        panic!("nothing was replayed");
+    };
+    assert_eq!(Value::from(message.clone()), screen);
+    let Message::Notification { params, .. } = message else {
+        panic!("expected the redraw notification, got {message:?}");
+    };
+
+    let mut ui = Ui::default();
+    for event in parse_redraw(&params) {
+        ui.apply(&event);
+    }
+    assert_eq!(ui.grid(1).unwrap().row_text(0), "h    ");
+
+    // the end of a session leaves the last screen up
+    assert_eq!(np.wait(Duration::from_millis(50)), None);
+    assert!(np.is_running());
+    assert!(np.try_wait().unwrap().is_none());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn malformed_sessions_are_rejected() {
+    let path = session_path("malformed");
+    std::fs::write(&path, encode(&Value::Array(vec![5.into(), 0.into()]))).unwrap();
+    assert!(read_session(&path).is_err());
+    assert!(NeovimProcess::replay(&path).is_err());
+    std::fs::remove_file(path).unwrap();
+}
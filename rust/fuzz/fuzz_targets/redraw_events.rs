@@ -0,0 +1,15 @@
+//! Decodes the params of `redraw` notifications built from arbitrary bytes
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vimdow_core::redraw::parse_redraw;
+
+fuzz_target!(|data: &[u8]| {
+    let mut stream = data;
+    while let Ok(v) = rmpv::decode::read_value(&mut stream) {
+        if let Some(params) = v.as_array() {
+            parse_redraw(params);
+        }
+    }
+});
@@ -0,0 +1,17 @@
+//! Reads a stream the way the client reads neovim's stdout, classifying every value
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rmpv::Value;
+use vimdow_core::rpc::Message;
+
+fuzz_target!(|data: &[u8]| {
+    let mut stream = data;
+    while let Ok(v) = rmpv::decode::read_value(&mut stream) {
+        if let Ok(message) = Message::parse(v.clone()) {
+            // nothing is lost when a message is sent on
+            assert_eq!(Value::from(message), v);
+        }
+    }
+});
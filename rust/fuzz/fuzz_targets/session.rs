@@ -0,0 +1,14 @@
+//! Reads session files that could have been truncated or edited by hand
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vimdow_core::session::read_entries;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(entries) = read_entries(data) {
+        for entry in entries {
+            let _ = entry.value();
+        }
+    }
+});
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use godot::classes::{ConfigFile, DirAccess, ProjectSettings};
+use godot::global::Error;
+use godot::prelude::*;
+
+const SECTION: &str = "breakpoints";
+
+/// Godot's debugger can only break unconditionally, so these are checked by vimdow
+/// whenever the game stops on the breakpoint
+#[derive(Default, Clone)]
+struct Breakpoint {
+    condition: String,
+    // the amount of times the breakpoint is reached before breaking, 0 breaks every time
+    hit_count: i32,
+}
+
+impl Breakpoint {
+    fn from_variant(v: &Variant) -> Option<(i32, Self)> {
+        // older files only saved the lines
+        if let Ok(line) = v.try_to::<i32>() {
+            return Some((line, Self::default()));
+        }
+
+        let dict = v.try_to::<VarDictionary>().ok()?;
+        let bp = Self {
+            condition: dict
+                .get("condition")
+                .map(|c| c.to_string())
+                .unwrap_or_default(),
+            hit_count: dict
+                .get("hit_count")
+                .and_then(|c| c.try_to().ok())
+                .unwrap_or_default(),
+        };
+        Some((dict.get("line")?.try_to().ok()?, bp))
+    }
+
+    fn to_dict(&self, line: i32) -> VarDictionary {
+        vdict! {
+            "line" => line,
+            "condition" => self.condition.clone(),
+            "hit_count" => self.hit_count,
+        }
+    }
+}
+
+/// Breakpoints shared by godot's debugger and neovim, saved in the project's `.godot` folder
+/// so they survive restarts. Paths are kept project relative (`res://`), lines start at 1
+#[derive(GodotClass)]
+#[class(tool, init, base=RefCounted)]
+pub struct BreakpointStore {
+    base: Base<RefCounted>,
+
+    #[init(val = "res://.godot/vimdow/breakpoints.cfg".into())]
+    #[var]
+    save_path: GString,
+
+    lines: BTreeMap<String, BTreeMap<i32, Breakpoint>>,
+}
+
+fn localize(path: &GString) -> String {
+    ProjectSettings::singleton().localize_path(path).to_string()
+}
+
+#[godot_api]
+impl BreakpointStore {
+    /// Replaces the breakpoints with the ones saved at `save_path`
+    #[func]
+    fn load(&mut self) {
+        self.lines.clear();
+        let mut conf = ConfigFile::new_gd();
+        if conf.load(&self.save_path) != Error::OK || !conf.has_section(SECTION) {
+            return;
+        }
+
+        for path in conf.get_section_keys(SECTION).as_slice() {
+            let lines: VarArray = conf.get_value(SECTION, path).try_to().unwrap_or_default();
+            let lines: BTreeMap<i32, Breakpoint> = lines
+                .iter_shared()
+                .filter_map(|l| Breakpoint::from_variant(&l))
+                .collect();
+            if !lines.is_empty() {
+                self.lines.insert(path.to_string(), lines);
+            }
+        }
+    }
+
+    #[func]
+    fn save(&self) {
+        let mut conf = ConfigFile::new_gd();
+        for (path, lines) in &self.lines {
+            let lines: VarArray = lines
+                .iter()
+                .map(|(line, bp)| bp.to_dict(*line).to_variant())
+                .collect();
+            conf.set_value(SECTION, path.as_str(), &lines.to_variant());
+        }
+
+        let dir = self.save_path.get_base_dir();
+        if DirAccess::make_dir_recursive_absolute(&dir) != Error::OK {
+            godot_error!("Couldn't create '{dir}' for breakpoints");
+            return;
+        }
+        let err = conf.save(&self.save_path);
+        if err != Error::OK {
+            godot_error!("Couldn't save breakpoints to '{}': {err:?}", self.save_path);
+        }
+    }
+
+    /// Sets or removes a breakpoint, `path` can be absolute or project relative
+    #[func]
+    fn set_breakpoint(&mut self, path: GString, line: i32, enabled: bool) {
+        let path = localize(&path);
+        if enabled {
+            self.lines.entry(path).or_default().entry(line).or_default();
+        } else if let Some(lines) = self.lines.get_mut(&path) {
+            lines.remove(&line);
+            if lines.is_empty() {
+                self.lines.remove(&path);
+            }
+        }
+        self.save();
+    }
+
+    /// Follows breakpoints whose lines were moved by edits, `moves` are `[from, to]` pairs.
+    /// Every breakpoint is taken off its old line before any is put on its new one
+    #[func]
+    fn move_breakpoints(&mut self, path: GString, moves: VarArray) {
+        let Some(lines) = self.lines.get_mut(&localize(&path)) else {
+            return;
+        };
+
+        let moved: Vec<(i32, Breakpoint)> = moves
+            .iter_shared()
+            .filter_map(|m| {
+                let m = m.try_to::<VarArray>().ok()?;
+                let from: i32 = m.get(0)?.try_to().ok()?;
+                let to: i32 = m.get(1)?.try_to().ok()?;
+                Some((to, lines.remove(&from)?))
+            })
+            .collect();
+        lines.extend(moved);
+        self.save();
+    }
+
+    /// Sets the condition and hit count of a breakpoint, adding it if needed
+    #[func]
+    fn set_options(&mut self, path: GString, line: i32, condition: GString, hit_count: i32) {
+        self.lines.entry(localize(&path)).or_default().insert(
+            line,
+            Breakpoint {
+                condition: condition.to_string(),
+                hit_count: hit_count.max(0),
+            },
+        );
+        self.save();
+    }
+
+    /// The `condition` and `hit_count` of a breakpoint, or an empty dictionary if there's none
+    #[func]
+    fn get_options(&self, path: GString, line: i32) -> VarDictionary {
+        self.lines
+            .get(&localize(&path))
+            .and_then(|lines| lines.get(&line))
+            .map(|bp| bp.to_dict(line))
+            .unwrap_or_default()
+    }
+
+    /// Removes the breakpoints in `path`, or every breakpoint if it's empty
+    #[func]
+    fn clear(&mut self, path: GString) {
+        if path.is_empty() {
+            self.lines.clear();
+        } else {
+            self.lines.remove(&localize(&path));
+        }
+        self.save();
+    }
+
+    #[func]
+    fn has_breakpoint(&self, path: GString, line: i32) -> bool {
+        self.lines
+            .get(&localize(&path))
+            .is_some_and(|lines| lines.contains_key(&line))
+    }
+
+    #[func]
+    fn get_lines(&self, path: GString) -> PackedInt32Array {
+        self.lines
+            .get(&localize(&path))
+            .map(|lines| lines.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Project relative paths of every script with breakpoints
+    #[func]
+    fn get_paths(&self) -> PackedStringArray {
+        self.lines.keys().map(GString::from).collect()
+    }
+
+    /// Every breakpoint keyed by absolute path, which is how neovim names its buffers.
+    /// Each is a dictionary of `line`, `condition` and `hit_count`
+    #[func]
+    fn get_global_breakpoints(&self) -> VarDictionary {
+        let ps = ProjectSettings::singleton();
+        let mut out = VarDictionary::new();
+        for (path, lines) in &self.lines {
+            let lines: VarArray = lines
+                .iter()
+                .map(|(line, bp)| bp.to_dict(*line).to_variant())
+                .collect();
+            out.set(&ps.globalize_path(path.as_str()), &lines);
+        }
+        out
+    }
+}
@@ -1,6 +1,9 @@
 use godot::classes::{Control, Font, FontVariation, IControl};
 use godot::prelude::*;
 use itertools::Itertools;
+use vimdow_core::grid::{Grid, Ui};
+
+use crate::render::{FontMetrics, FontStyle, HlAttr, Region};
 
 /// Creates rendering data based on the hl attributes
 /// defined by neovim. Returns structs applied with the appropriate theme
@@ -9,20 +12,12 @@ use itertools::Itertools;
 pub struct Highlighter {
     base: Base<Control>,
 
-    #[export]
-    #[var(pub)]
-    hl_data: VarDictionary,
-
-    #[export]
-    #[var(pub)]
-    hl_regions: Array<PackedInt32Array>,
-
     bold_font: Gd<FontVariation>,
     italic_font: Gd<FontVariation>,
     normal_font: Gd<FontVariation>,
 }
 
-const THEME_TYPE: &'static str = "VimdowEditor";
+const THEME_TYPE: &str = "VimdowEditor";
 
 // wraps a font in a font variation
 fn fv(font: &Gd<Font>) -> Gd<FontVariation> {
@@ -83,137 +78,43 @@ impl Highlighter {
     }
 }
 
-pub struct HlAttr {
-    pub foreground: Color,
-    pub background: Color,
-    pub special: Color,
-    pub font: Gd<FontVariation>,
-    pub undercurl: bool,
-    pub underline: bool,
-    pub underdouble: bool,
-    pub underdotted: bool,
-    pub underdashed: bool,
-    pub strikethrough: bool,
-    pub url: bool,
-    pub font_size: i32,
-    pub char_size: Vector2,
-}
-
-pub struct Region {
-    pub start_col: usize,
-    pub end_col: usize,
-    pub attr: HlAttr,
-}
-impl Region {
-    pub fn len(&self) -> usize {
-        self.end_col - self.start_col
-    }
-}
-
-type VD = VarDictionary;
 #[godot_api]
 impl Highlighter {
-    // check ":h ui-event-hl_attr_define"
-    pub fn get_hl_attr(&self, hl_id: i32) -> HlAttr {
-        let attr: VD = self.hl_data.at(hl_id).to();
-        let font = if attr.contains_key("bold") {
-            &self.bold_font
-        } else if attr.contains_key("italic") {
-            &self.italic_font
-        } else {
-            &self.normal_font
-        }
-        .clone();
-
-        let (mut foreground, mut background) = (
-            match self.get_attr_color(hl_id, HlAttrColor::Foreground) {
-                Ok(fg) => fg,
-                Err(bg) => bg,
-            },
-            match self.get_attr_color(hl_id, HlAttrColor::Background) {
-                Ok(fg) => fg,
-                Err(bg) => bg,
-            },
-        );
-
-        if attr.contains_key("reverse") {
-            (foreground, background) = (background, foreground);
-        }
-
-        let mut special = match self.get_attr_color(hl_id, HlAttrColor::Special) {
-            Ok(fg) => fg,
-            Err(bg) => bg,
-        };
-
-        if let Some(blend_pct) = attr
-            .get("blend")
-            .map(|i| (100.0 - i.to::<i32>() as f32) / 100.0)
-        {
-            foreground.a = blend_pct;
-            background.a = blend_pct;
-            special.a = blend_pct;
-        }
-
+    pub fn get_hl_attr(&self, ui: &Ui, hl_id: u64) -> HlAttr {
+        let attrs = ui.hl_attr(hl_id);
+        let font = FontStyle::from_attrs(attrs);
         let font_size = self
             .base()
             .get_theme_font_size_ex("font_size")
             .theme_type(THEME_TYPE)
             .done();
 
-        let char_size = font
-            .get_base_font()
-            .unwrap()
-            .get_char_size(' ' as u32, font_size);
-
-        let undercurl = attr.contains_key("undercurl");
-
-        HlAttr {
-            foreground,
-            background,
-            special,
-            font,
-            undercurl,
-            underline: attr.contains_key("underline"),
-            underdouble: attr.contains_key("underdouble"),
-            underdotted: attr.contains_key("underdotted"),
-            underdashed: attr.contains_key("underdashed"),
-            strikethrough: attr.contains_key("strikethrough"),
-            url: attr.contains_key("strikethrough"),
+        let font_variation = self.font(font);
+        let metrics = FontMetrics {
             font_size,
-            char_size,
-        }
-    }
-
-    fn get_default_color(&self, c: HlAttrColor) -> Color {
-        self.hl_data.at(0).to::<VD>().at(c.to_string()).to()
-    }
-
-    fn get_attr_color(&self, hl_id: i32, c: HlAttrColor) -> Result<Color, Color> {
-        self.hl_data
-            .at(hl_id)
-            .to::<VD>()
-            .get(c.to_string())
-            .map(|rgb| rgb.to())
-            .ok_or_else(|| self.get_default_color(c))
+            char_size: font_variation
+                .get_base_font()
+                .unwrap()
+                .get_char_size(' ' as u32, font_size),
+            ascent: font_variation.get_ascent_ex().font_size(font_size).done(),
+            descent: font_variation.get_descent_ex().font_size(font_size).done(),
+        };
+        HlAttr::new(attrs, ui.default_colors(), metrics)
     }
 
-    #[func]
-    pub fn clear(&mut self) {
-        // self.hl_regions.clear();
-        for y in 0..self.hl_regions.len() {
-            self.hl_regions.at(y).fill(0);
+    pub fn font(&self, style: FontStyle) -> Gd<FontVariation> {
+        match style {
+            FontStyle::Normal => &self.normal_font,
+            FontStyle::Bold => &self.bold_font,
+            FontStyle::Italic => &self.italic_font,
         }
+        .clone()
     }
 
-    pub fn get_regions(&self, row: i32) -> Vec<Region> {
-        let Some(row_regions) = self.hl_regions.get(row as usize) else {
-            return vec![];
-        };
-
-        row_regions
-            .as_slice()
-            .iter()
-            .map(|&n| n as i32)
+    /// The runs of cells in a row of the grid that share a highlight
+    pub fn get_regions(&self, ui: &Ui, grid: &Grid, row: usize) -> Vec<Region> {
+        (0..grid.width())
+            .map(|col| grid.cell(row, col).map(|c| c.hl_id).unwrap_or_default())
             .enumerate()
             .chunk_by(|(_, hl_id)| *hl_id)
             .into_iter()
@@ -223,32 +124,14 @@ impl Highlighter {
                 Region {
                     start_col,
                     end_col,
-                    attr: self.get_hl_attr(hl_id),
+                    attr: self.get_hl_attr(ui, hl_id),
                 }
             })
             .collect()
     }
 
-    pub fn get_cursor_attr(&self, cursor: &Vector2i) -> HlAttr {
-        let cursor_hl_id = self.hl_regions.at(cursor.y as usize)[cursor.x as usize];
-        self.get_hl_attr(cursor_hl_id as i32)
-    }
-}
-
-// choices of default color to pick
-enum HlAttrColor {
-    Foreground,
-    Background,
-    Special,
-}
-
-impl ToString for HlAttrColor {
-    fn to_string(&self) -> String {
-        match self {
-            Self::Foreground => "foreground",
-            Self::Background => "background",
-            Self::Special => "special",
-        }
-        .into()
+    pub fn get_cursor_attr(&self, ui: &Ui, grid: &Grid, row: usize, col: usize) -> HlAttr {
+        let hl_id = grid.cell(row, col).map(|c| c.hl_id).unwrap_or_default();
+        self.get_hl_attr(ui, hl_id)
     }
 }
@@ -1,46 +1,59 @@
-use std::f64::consts::TAU;
-
-use godot::classes::{Control, IControl, ProjectSettings};
-use godot::global::cos;
+use godot::classes::{Control, IControl, Input, ProjectSettings, Resource};
+use godot::global::Key;
 use godot::{obj::WithBaseField, prelude::*};
 
-mod err;
+mod breakpoints;
 mod highlights;
 mod neovim;
-
-use ropey::Rope;
-use unicode_segmentation::UnicodeSegmentation;
-
-use crate::highlights::{Highlighter, HlAttr};
-
-fn column_slice(row: &str, start: usize, end: usize) -> String {
-    assert!(start <= end);
-    row.graphemes(true).skip(start).take(end - start).collect()
+pub mod render;
+mod resources;
+
+use vimdow_core::log;
+
+use crate::highlights::Highlighter;
+use crate::neovim::NeovimClient;
+use crate::render::{Cursor, CursorShape, DrawCommand};
+
+// paths of the files in a drag payload from the FileSystem dock, or of a dragged resource
+fn dropped_paths(data: &Variant) -> Option<PackedStringArray> {
+    let payload: VarDictionary = data.try_to().ok()?;
+    match payload.get("type")?.to_string().as_str() {
+        "files" | "files_and_dirs" => payload.get("files")?.try_to().ok(),
+        "resource" => {
+            let res: Gd<Resource> = payload.get("resource")?.try_to().ok()?;
+            let path = res.get_path();
+            // built-in resources live inside of another file
+            (!path.is_empty() && !path.contains("::")).then(|| PackedStringArray::from([path]))
+        }
+        _ => None,
+    }
 }
 
-fn get_column(src: &str, pos: &Vector2i) -> String {
-    src.lines()
-        .nth(pos.y as usize)
-        .map(|line| {
-            line.graphemes(true)
-                .nth(pos.x as usize)
-                .unwrap_or(" ")
-                .to_string()
-        })
-        .unwrap_or_else(String::new)
+// the command dropped files are opened with, picked by the held modifiers
+fn drop_command() -> &'static str {
+    let input = Input::singleton();
+    if input.is_key_pressed(Key::SHIFT) {
+        "split"
+    } else if input.is_key_pressed(Key::CTRL) {
+        "vsplit"
+    } else if input.is_key_pressed(Key::ALT) {
+        "tabedit"
+    } else {
+        "edit"
+    }
 }
 
+// neovim only sends the global grid without ext_multigrid
+const GRID: u64 = 1;
+
 #[derive(GodotClass)]
 #[class(tool, init, base=Control)]
 // Funny name
 struct VimdowWindow {
     base: Base<Control>,
 
-    grid_text: Rope,
-
-    #[init(val = Vector2i {x: -1, y: -1})]
-    #[var]
-    cursor: Vector2i,
+    // the client whose grid is drawn, set by `flush`
+    client: Option<Gd<NeovimClient>>,
 
     #[export]
     current_mode: VarDictionary,
@@ -51,290 +64,181 @@ struct VimdowWindow {
 
 #[godot_api]
 impl VimdowWindow {
-    #[func]
-    fn get_line_count(&self) -> i32 {
-        self.grid_text.len_lines() as i32
-    }
+    /// Text dragged onto the window, meant to be pasted into neovim
+    #[signal]
+    fn text_dropped(text: String);
 
-    #[func]
-    fn get_line(&self, i: i32) -> String {
-        self.grid_text.line(i as usize).to_string()
-    }
+    /// Files dragged onto the window, with the command they should be opened with
+    #[signal]
+    fn files_dropped(paths: PackedStringArray, command: String);
 
+    /// Handles files dropped from outside of godot, only accepting
+    /// them if they were dropped on top of the window
     #[func]
-    fn get_grid_size(&self) -> Vector2i {
-        let y = self.get_line_count();
-        let x = self.get_line(0).len() as i32;
-        Vector2i { x, y }
-    }
-
-    #[func]
-    fn set_line(&mut self, i: i32, text: String) {
-        let i = i as usize;
-        let start = self.grid_text.line_to_char(i);
-        let end = self.grid_text.line_to_char(i + 1);
-        self.grid_text.remove(start..end);
-        self.grid_text.insert(start, &text);
+    fn drop_os_files(&mut self, files: PackedStringArray) {
+        let base = self.base();
+        if !base.is_visible_in_tree()
+            || !base
+                .get_global_rect()
+                .contains_point(base.get_global_mouse_position())
+        {
+            return;
+        }
+        drop(base);
+        self.emit_files_dropped(files);
     }
 
-    #[func]
-    fn set_grid_size(&mut self, width: i32, height: i32) {
-        self.highlighter.bind_mut().set_hl_regions(
-            (0..=height)
-                .map(|_| {
-                    let mut out = PackedInt32Array::new();
-                    out.resize(width as usize);
-                    out.fill(0);
-                    out
-                })
-                .collect(),
-        );
-
-        let mut row = " ".repeat(width as usize);
-        row.push('\n');
-        let mut text = row.repeat(height as usize);
-        assert_eq!(
-            text.remove(text.len() - 1),
-            '\n',
-            "Should remove last newline"
-        );
-        self.grid_text = text.into();
+    fn emit_files_dropped(&mut self, files: PackedStringArray) {
+        let ps = ProjectSettings::singleton();
+        let paths = files
+            .as_slice()
+            .iter()
+            .map(|p| ps.globalize_path(p))
+            .collect();
+        self.signals()
+            .files_dropped()
+            .emit(&paths, drop_command().to_string());
     }
 
     #[func]
-    fn clear(&mut self) {
-        for i in 0..self.get_line_count() {
-            let line = self.get_line(i).to_string();
-            let new_line: String = line
-                .chars()
-                .map(|c| if c.is_whitespace() { c } else { ' ' })
-                .collect();
-            self.set_line(i, new_line);
-        }
-
-        self.highlighter.bind_mut().clear();
+    // starts a redraw of the client's grid, with the cursor drawn for `current_mode`
+    fn flush(&mut self, client: Gd<NeovimClient>, current_mode: VarDictionary) {
+        self.client = Some(client);
+        self.current_mode = current_mode;
+        self.base_mut().queue_redraw();
     }
 
+    /// What `draw` draws for the current grid, one command per line.
+    /// Meant for debugging the rendering without looking at it
     #[func]
-    // starts a redraw with the provided highlight data
-    fn flush(&mut self, hl: VarDictionary, current_mode: VarDictionary) {
-        self.highlighter.bind_mut().set_hl_data(hl);
-        self.current_mode = current_mode;
-        self.base_mut().queue_redraw();
+    fn get_draw_commands(&self) -> PackedStringArray {
+        self.draw_commands()
+            .iter()
+            .map(|c| GString::from(&c.to_string()))
+            .collect()
     }
 
-    fn draw_row(&mut self, row: i32) {
+    fn draw_commands(&self) -> Vec<DrawCommand> {
         let ignore_hl: bool = ProjectSettings::singleton()
             .get_setting("vimdow/debug/ignore_hl")
             .try_to()
             .unwrap_or(false);
 
-        let regions = self.highlighter.bind().get_regions(row);
-        if !ignore_hl {
-            // drawing background colors
-            for r in regions.iter() {
-                let position = Vector2 {
-                    x: r.attr.char_size.x * r.start_col as f32,
-                    y: r.attr.char_size.y * row as f32,
-                };
-                let size = Vector2 {
-                    x: r.attr.char_size.x * (r.end_col - r.start_col) as f32,
-                    y: r.attr.char_size.y,
-                };
-
-                // drawing colored background
-                self.base_mut()
-                    .draw_rect_ex(Rect2 { position, size }, r.attr.background)
-                    .filled(true)
-                    .done();
-            }
-        }
-
-        for r in regions.iter() {
-            let text_position = Vector2 {
-                x: r.start_col as f32 * r.attr.char_size.x,
-                y: row as f32 * r.attr.char_size.y
-                    + r.attr
-                        .font
-                        .get_ascent_ex()
-                        .font_size(r.attr.font_size)
-                        .done(),
-            };
-
-            let region_text = column_slice(&self.get_line(row), r.start_col, r.end_col);
-
-            self.base_mut()
-                .draw_string_ex(&r.attr.font, text_position, &region_text)
-                .font_size(r.attr.font_size)
-                .modulate(if ignore_hl {
-                    Color::WHITE
-                } else {
-                    r.attr.foreground
-                })
-                .done();
-
-            if row == self.cursor.y
-                && r.start_col <= self.cursor.x as usize
-                && self.cursor.x as usize <= r.end_col
-            {
-                self.draw_cursor();
-            }
+        let Some(client) = &self.client else {
+            return vec![];
+        };
+        let client = client.bind();
+        let ui = client.ui();
+        let Some(grid) = ui.grid(GRID) else {
+            return vec![];
+        };
+
+        let highlighter = self.highlighter.bind();
+        let shape = self
+            .current_mode
+            .get("cursor_shape")
+            .and_then(|s| CursorShape::from_name(&s.to_string()));
+        let (cursor_grid, cursor_row, cursor_col) = ui.cursor();
+        let (cursor_row, cursor_col) = (cursor_row as usize, cursor_col as usize);
+
+        (0..grid.height())
+            .flat_map(|row| {
+                let cursor = shape
+                    .filter(|_| cursor_grid == GRID && row == cursor_row)
+                    .map(|shape| Cursor {
+                        col: cursor_col,
+                        shape,
+                        attr: highlighter.get_cursor_attr(ui, grid, row, cursor_col),
+                    });
+                render::draw_row(
+                    row,
+                    &grid.row_text(row),
+                    &highlighter.get_regions(ui, grid, row),
+                    cursor.as_ref(),
+                    ignore_hl,
+                )
+            })
+            .collect()
+    }
+}
 
-            let line_width = r.attr.font_size as f32 * 0.1;
-            let l = r.len();
-            let desc = r.attr.font.get_descent() as f64;
-            if r.attr.undercurl {
-                let amplitude = desc / 2.0;
-                const STEPS_PER_CYCLE: usize = 8;
-                let total_span = l * STEPS_PER_CYCLE;
-                let mut points = PackedVector2Array::new();
-                for i in 0..total_span {
-                    let t = i as f64 / total_span as f64;
-                    let y = text_position.y as f64
-                        + amplitude
-                        + -cos(l as f64 * t * TAU) * amplitude as f64;
-                    let p = Vector2::new(
-                        text_position.x + t as f32 * l as f32 * r.attr.char_size.x,
-                        y as f32,
-                    );
-                    points.push(p);
+#[godot_api]
+impl IControl for VimdowWindow {
+    fn draw(&mut self) {
+        let commands = self.draw_commands();
+        let highlighter = (*self.highlighter).clone();
+        let mut base = self.base_mut();
+        for command in commands {
+            match command {
+                DrawCommand::Rect {
+                    position,
+                    size,
+                    color,
+                } => {
+                    base.draw_rect_ex(Rect2 { position, size }, color)
+                        .filled(true)
+                        .done();
                 }
-                self.base_mut()
-                    .draw_polyline_ex(&points, r.attr.special)
-                    .width(line_width)
-                    .done();
-            }
-
-            let desc = r.attr.font.get_descent_ex()
-                .font_size(r.attr.font_size)
-                .done();
-
-            if r.attr.strikethrough {
-                self.draw_underline(text_position, l as f32, -desc / 2.0, &r.attr);
-            }
-
-            if r.attr.underline || r.attr.url {
-                self.draw_underline(text_position, l as f32, desc, &r.attr);
-            }
-
-            if r.attr.underdouble {
-                self.draw_underline(text_position, l as f32, desc, &r.attr);
-                let next_text_position =
-                    Vector2::new(text_position.x, text_position.y - r.attr.char_size.y * 0.2);
-                self.draw_underline(next_text_position, l as f32, desc, &r.attr);
-            }
-
-            if r.attr.underdashed {
-                self.draw_segmented_underline(text_position, 0.33, 2, l, &r.attr);
-            }
-
-            if r.attr.underdotted {
-                self.draw_segmented_underline(text_position, 0.15, 3, l, &r.attr);
+                DrawCommand::Text {
+                    font,
+                    font_size,
+                    position,
+                    text,
+                    color,
+                } => {
+                    let font = highlighter.bind().font(font);
+                    base.draw_string_ex(&font, position, &text)
+                        .font_size(font_size)
+                        .modulate(color)
+                        .done();
+                }
+                DrawCommand::Polyline {
+                    points,
+                    color,
+                    width,
+                } => {
+                    base.draw_polyline_ex(&PackedVector2Array::from(points), color)
+                        .width(width)
+                        .done();
+                }
+                DrawCommand::Line { from, to, color } => base.draw_line(from, to, color),
             }
         }
     }
 
-    fn draw_underline(&mut self, text_position: Vector2, region_len: f32, desc: f32, attr: &HlAttr) {
-        let line = PackedVector2Array::from([
-            Vector2::new(text_position.x, text_position.y + desc as f32),
-            Vector2::new(
-                text_position.x + region_len * attr.char_size.x,
-                text_position.y + desc as f32,
-            ),
-        ]);
-        self.base_mut()
-            .draw_polyline_ex(&line, attr.special)
-            .width(2.0)
-            .done();
+    fn can_drop_data(&self, _at_position: Vector2, data: Variant) -> bool {
+        data.get_type() == VariantType::STRING || dropped_paths(&data).is_some()
     }
 
-    fn draw_segmented_underline(
-        &mut self,
-        text_position: Vector2,
-        segment_len: f32,
-        segments_per_char: i32,
-        region_len: usize,
-        attr: &HlAttr,
-    ) {
-        let desc = attr.font.get_descent_ex().font_size(attr.font_size).done();
-        let y = text_position.y + desc as f32;
-        for i in 0..region_len {
-            let left = Vector2::new(text_position.x + i as f32 * attr.char_size.x, y);
-            let right = Vector2::new(left.x + attr.char_size.x * segment_len, y);
-
-            for j in 0..segments_per_char {
-                let offset = j as f32 / segments_per_char as f32 * attr.char_size.x;
-                let left = Vector2::new(left.x + offset, left.y);
-                let right = Vector2::new(right.x + offset, right.y);
-                self.base_mut().draw_line(left, right, attr.special);
-            }
+    fn drop_data(&mut self, _at_position: Vector2, data: Variant) {
+        if let Some(files) = dropped_paths(&data) {
+            self.emit_files_dropped(files);
+        } else {
+            self.signals().text_dropped().emit(data.to::<String>());
         }
     }
+}
 
-    fn draw_cursor(&mut self) {
-        let attr = self.highlighter.bind().get_cursor_attr(&self.cursor);
-        let cs = attr.char_size;
-        let position = { Vector2::new(self.cursor.x as f32, self.cursor.y as f32) * cs };
-        match self.current_mode.at("cursor_shape").to_string().as_str() {
-            "block" => {
-                self.base_mut()
-                    .draw_rect_ex(Rect2 { position, size: cs }, attr.foreground)
-                    .filled(true)
-                    .done();
+struct Extension;
 
-                let region_text = get_column(&self.grid_text.to_string(), &self.cursor);
-                self.base_mut()
-                    .draw_string_ex(
-                        &attr.font,
-                        Vector2 {
-                            x: position.x,
-                            y: position.y
-                                + attr.font.get_ascent_ex().font_size(attr.font_size).done(),
-                        },
-                        &region_text,
-                    )
-                    .font_size(attr.font_size)
-                    .modulate(attr.background)
-                    .done();
-            }
-            "vertical" => {
-                self.base_mut().draw_line(
-                    position,
-                    Vector2 {
-                        x: position.x,
-                        y: position.y + cs.y,
-                    },
-                    attr.foreground,
-                );
-            }
-            "horizontal" => {
-                self.base_mut().draw_line(
-                    Vector2 {
-                        x: position.x,
-                        y: position.y + cs.y,
-                    },
-                    Vector2 {
-                        x: position.x + cs.x,
-                        y: position.y + cs.y,
-                    },
-                    attr.foreground,
-                );
-            }
-            _ => unreachable!(),
-        }
+// sends the messages of code that doesn't depend on godot to godot's output
+struct GodotLog;
+
+impl log::Log for GodotLog {
+    fn error(&self, msg: &str) {
+        godot_error!("{msg}");
     }
-}
 
-#[godot_api]
-impl IControl for VimdowWindow {
-    fn draw(&mut self) {
-        for i in 0..self.get_line_count() {
-            self.draw_row(i);
-        }
+    fn info(&self, msg: &str) {
+        godot_print!("{msg}");
     }
 }
 
-struct Extension;
-
 #[gdextension]
-unsafe impl ExtensionLibrary for Extension {}
+unsafe impl ExtensionLibrary for Extension {
+    fn on_stage_init(stage: InitStage) {
+        if stage == InitStage::Scene {
+            log::set_log(Box::new(GodotLog));
+        }
+    }
+}
@@ -1,17 +1,42 @@
-use godot::classes::{InputEvent, InputEventKey};
+use std::collections::{HashMap, HashSet};
+
+use godot::classes::{InputEvent, InputEventKey, ProjectSettings};
 use godot::global::Key;
 use godot::prelude::*;
 use rmpv::Value;
+mod api;
+mod batch;
+mod buffers;
+mod clipboard;
+mod debugger;
+mod diagnostics;
 mod ext_types;
+mod handlers;
+#[cfg(feature = "itest")]
+mod itest;
+mod lsp;
 mod msgpack;
 
-use crate::neovim::key_events::NvimInput;
-use crate::neovim::mouse_events::NvimInputMouse;
-use crate::neovim::msgpack::rpc_array_to_vararray;
-use msgpack::rmpv_to_godot;
+use crate::neovim::api::NvimApi;
+use crate::neovim::batch::{NeovimBatch, batch_response};
+use crate::neovim::buffers::Buffers;
+use crate::neovim::clipboard::Clipboard;
+use crate::neovim::ext_types::{Buffer, ExtTypes, ext_handle};
+use crate::neovim::handlers::{EDITOR_METHODS, RequestHandler};
+use crate::neovim::key_events::nvim_input;
+use crate::neovim::lsp::LanguageServer;
+use crate::neovim::mouse_events::{MouseState, NvimInputMouse};
+use msgpack::{godot_to_rmpv, rmpv_to_godot, rpc_array_to_vararray, vararray_to_rpc};
 
-mod process;
-use process::NeovimProcess;
+use vimdow_core::api_info::ApiInfo;
+use vimdow_core::batch::Batch;
+use vimdow_core::err::VimdowError;
+use vimdow_core::grid::Ui;
+use vimdow_core::paste::{self, paste_chunks};
+use vimdow_core::process::NeovimProcess;
+use vimdow_core::redraw::parse_redraw;
+use vimdow_core::requests::{Route, route};
+use vimdow_core::rpc::Message;
 
 mod key_events;
 mod mouse_events;
@@ -21,6 +46,161 @@ mod mouse_events;
 pub struct NeovimClient {
     base: Base<Node>,
     nvim_process: Option<NeovimProcess>,
+    mouse: MouseState,
+    clipboard: Clipboard,
+    buffers: Buffers,
+    language_server: LanguageServer,
+    request_handlers: HashMap<String, RequestHandler>,
+    // msgids of `nvim_call_atomic` requests sent by a `NeovimBatch`
+    batches: HashSet<u32>,
+    // what the running neovim answered to `nvim_get_api_info`
+    api_info: Option<ApiInfo>,
+    api_info_msgid: Option<u32>,
+    // deprecated functions that were called, which only warn once
+    deprecated_calls: HashSet<String>,
+    // the grids, as drawn by the `redraw` events so far
+    ui: Ui,
+}
+
+impl NeovimClient {
+    fn start_process(&mut self, np: Result<NeovimProcess, VimdowError>) -> bool {
+        match np {
+            Ok(mut np) => {
+                // the API level is checked by the #[func]s of `NvimApi`
+                self.api_info = None;
+                self.ui = Ui::default();
+                self.language_server = LanguageServer::default();
+                self.api_info_msgid = Some(np.nvim_get_api_info() as u32);
+                self.nvim_process = Some(np);
+                true
+            }
+            Err(e) => {
+                godot_error!("Couldn't start neovim process: {e}");
+                false
+            }
+        }
+    }
+
+    // runs a handler registered with `register_request_handler` or `register_editor_methods`
+    fn call_request_handler(
+        &mut self,
+        msgid: u32,
+        method: &str,
+        handler: &RequestHandler,
+        params: &[Value],
+    ) {
+        let reply = rpc_array_to_vararray(params.to_vec(), &self.ext_types())
+            .map_err(|e| e.to_string().to_variant())
+            .and_then(|args| handler.check_args(method, args).map_err(|e| e.to_variant()))
+            .and_then(|args| {
+                // the handler is free to call back into the client
+                let _guard = self.base_mut();
+                handler.call(&args)
+            });
+
+        let (error, result) = match reply {
+            Ok(result) => (Variant::nil(), result),
+            Err(error) => (error, Variant::nil()),
+        };
+        self.respond_variants(msgid, error, result);
+    }
+
+    /// What neovim drew so far
+    pub fn ui(&self) -> &Ui {
+        &self.ui
+    }
+
+    // decodes handles with the ext type ids of the running neovim
+    fn ext_types(&self) -> ExtTypes {
+        ExtTypes::new(self.api_info.as_ref(), Some(self.to_gd()))
+    }
+
+    // `nvim_get_api_info` answers with the channel id and the metadata
+    fn read_api_info(&mut self, result: &Value) {
+        let Some([_, metadata]) = result.as_array().map(Vec::as_slice) else {
+            godot_error!("Unexpected nvim_get_api_info result: {result}");
+            return;
+        };
+        match ApiInfo::parse(metadata) {
+            Ok(info) => {
+                if info.api_level < api::API_LEVEL {
+                    godot_warn!(
+                        "Neovim has API level {}, the functions added after it can't be called",
+                        info.api_level
+                    );
+                }
+                self.api_info = Some(info);
+            }
+            Err(e) => godot_error!("{e}"),
+        }
+    }
+
+    fn send_batch(&mut self, batch: Batch) -> i32 {
+        // checked like the #[func]s are. `nvim_call_atomic` is deprecated in favour of lua,
+        // which batches can't be written in, so that isn't warned about
+        let (since, _) = api::api_levels("nvim_call_atomic").unwrap_or((1, None));
+        let Some(np) = self.api_process("nvim_call_atomic", since, None) else {
+            return -1;
+        };
+        let msgid = np.batch(batch);
+        self.batches.insert(msgid as u32);
+        msgid
+    }
+
+    fn respond_variants(&mut self, msgid: u32, error: Variant, result: Variant) {
+        let Some(np) = self.nvim_process.as_mut() else {
+            return;
+        };
+        // neovim is waiting on an answer either way, so it's told what couldn't be sent
+        let (error, result) = match (godot_to_rmpv(error), godot_to_rmpv(result)) {
+            (Ok(error), Ok(result)) => (error, result),
+            (Err(e), _) | (_, Err(e)) => {
+                godot_error!("Can't respond to request {msgid}: {e}");
+                (Value::from(e.to_string()), Value::Nil)
+            }
+        };
+        if let Err(e) = np.respond(msgid, error, result) {
+            godot_error!("{e}");
+        }
+    }
+
+    // handles notifications meant for the client itself, returns false for everything else
+    fn handle_notification(&mut self, method: &str, params: &[Value]) -> bool {
+        match method {
+            "vimdow_buf_write" => {
+                if let Some(path) = params.first().and_then(Value::as_str) {
+                    self.signals().buffer_written().emit(path.to_string());
+                }
+            }
+            "vimdow_buf_attach" => {
+                // the lua plugin sends the buffer number, not an ext
+                if let [buf, path, ..] = params
+                    && let Some(path) = path.as_str()
+                    && let Some(id) = ext_handle(buf)
+                    && let Some(handle) = self.ext_types().handle("Buffer", id)
+                    && let Some(np) = self.nvim_process.as_mut()
+                {
+                    self.buffers.track(id, path.to_string());
+                    np.nvim_buf_attach(&Buffer::new(handle, None).bind(), true, vec![]);
+                }
+            }
+            "nvim_buf_lines_event" => {
+                if let Some(path) = self.buffers.apply_lines_event(params) {
+                    let path = path.to_string();
+                    self.signals().buffer_changed().emit(path);
+                }
+            }
+            "nvim_buf_detach_event" => {
+                if let Some(path) = self.buffers.detach(params) {
+                    self.signals().buffer_detached().emit(path);
+                }
+            }
+            "nvim_buf_changedtick_event" => {}
+            "vimdow_lsp_exit" => self.language_server.lost(),
+            _ => return false,
+        }
+        true
+    }
 }
 
 #[godot_api]
@@ -37,6 +217,26 @@ impl NeovimClient {
     #[signal]
     fn neovim_quit(status: i32);
 
+    /// Neovim wrote a buffer to `path`
+    #[signal]
+    fn buffer_written(path: String);
+
+    /// The text of a buffer synced by `vimdow_buf_attach` changed, see `get_buffer_text`
+    #[signal]
+    fn buffer_changed(path: String);
+
+    /// A synced buffer was unloaded, its text is no longer available
+    #[signal]
+    fn buffer_detached(path: String);
+
+    /// The live (possibly unsaved) text of the buffer editing `path`,
+    /// empty if neovim isn't syncing it
+    #[func]
+    fn get_buffer_text(&self, path: GString) -> String {
+        let path = ProjectSettings::singleton().globalize_path(&path);
+        self.buffers.text(&path.to_string()).unwrap_or_default()
+    }
+
     #[func]
     fn kill_process(&mut self) {
         if self.nvim_process.is_some() {
@@ -48,32 +248,202 @@ impl NeovimClient {
     #[func]
     fn spawn(&mut self, program: String, args: PackedStringArray) -> bool {
         let args: Vec<_> = args.to_vec().into_iter().map(|g| g.to_string()).collect();
-        match NeovimProcess::new(&program, args.as_slice()) {
-            Ok(np) => {
-                self.nvim_process = Some(np);
-                true
-            }
+        self.start_process(NeovimProcess::new(&program, args.as_slice()))
+    }
+
+    /// Like `spawn`, but everything sent to and from neovim is recorded into a session file
+    #[func]
+    fn spawn_recorded(
+        &mut self,
+        program: String,
+        args: PackedStringArray,
+        session_path: GString,
+    ) -> bool {
+        let args: Vec<_> = args.to_vec().into_iter().map(|g| g.to_string()).collect();
+        let path = ProjectSettings::singleton().globalize_path(&session_path);
+        self.start_process(NeovimProcess::new_recorded(
+            &program,
+            args.as_slice(),
+            path.to_string(),
+        ))
+    }
+
+    /// Plays back a session file recorded by `spawn_recorded` instead of running neovim
+    #[func]
+    fn replay(&mut self, session_path: GString) -> bool {
+        let path = ProjectSettings::singleton().globalize_path(&session_path);
+        self.start_process(NeovimProcess::replay(path.to_string()))
+    }
+
+    #[func]
+    fn request(&mut self, method: String, params: VarArray) -> i32 {
+        let Some(np) = self.nvim_process.as_mut() else {
+            return -1;
+        };
+        match vararray_to_rpc(params) {
+            Ok(params) => np.request(&method, params),
             Err(e) => {
-                godot_error!("Couldn't start neovim process: {:?}", e);
-                false
+                godot_error!("Can't call {method}: {e}");
+                -1
             }
         }
     }
 
+    /// Collects API calls to send in one round trip, see `NeovimBatch`
     #[func]
-    fn request(&mut self, method: String, params: VarArray) -> i32 {
+    fn batch(&self) -> Gd<NeovimBatch> {
+        NeovimBatch::new(self.to_gd())
+    }
+
+    /// Makes neovim use godot's clipboard for the "+ and "* registers
+    #[func]
+    fn register_clipboard_provider(&mut self) {
         let Some(np) = self.nvim_process.as_mut() else {
-            return -1;
+            return;
         };
-        np.var_request(&method, params)
+        np.nvim_exec_lua(clipboard::PROVIDER_LUA, vec![]);
     }
 
+    /// Pastes text like a terminal's bracketed paste, skipping mappings and autoindent.
+    /// Large texts get streamed to neovim in chunks
     #[func]
-    fn respond(&mut self, msgid: i32, error: Variant, result: Variant) {
+    fn paste(&mut self, text: String) {
+        let Some(np) = self.nvim_process.as_mut() else {
+            return;
+        };
+
+        let chunks = paste_chunks(&text, paste::CHUNK_SIZE);
+        let last = chunks.len() - 1;
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            // see ":h nvim_paste()" for the phases
+            let phase = match i {
+                _ if last == 0 => -1,
+                0 => 1,
+                _ if i == last => 3,
+                _ => 2,
+            };
+            np.nvim_paste(chunk, true, phase);
+        }
+    }
+
+    /// Opens files in neovim with one of "edit", "split", "vsplit" or "tabedit"
+    #[func]
+    fn open_files(&mut self, paths: PackedStringArray, command: String) {
+        let Some(np) = self.nvim_process.as_mut() else {
+            return;
+        };
+        if !matches!(command.as_str(), "edit" | "split" | "vsplit" | "tabedit") {
+            godot_error!("Can't open files with '{command}'");
+            return;
+        }
+
+        for path in paths.as_slice() {
+            // nvim_cmd takes care of escaping the path
+            let cmd = vec![
+                ("cmd".into(), command.as_str().into()),
+                ("args".into(), Value::Array(vec![path.to_string().into()])),
+            ];
+            np.nvim_cmd(cmd, vec![]);
+        }
+    }
+
+    /// Opens the innermost of the stack `frames` of a paused game, marks the line
+    /// it stopped on, and puts the frames in the quickfix list
+    #[func]
+    fn show_break(&mut self, frames: Array<VarDictionary>) {
+        let Some(np) = self.nvim_process.as_mut() else {
+            return;
+        };
+        debugger::show_break(np, &frames);
+    }
+
+    /// Removes the mark left by `show_break`
+    #[func]
+    fn clear_break(&mut self) {
+        let Some(np) = self.nvim_process.as_mut() else {
+            return;
+        };
+        debugger::clear_break(np);
+    }
+
+    /// Shows the variables of a paused game in a floating window,
+    /// see `debugger::show_variables` for their format
+    #[func]
+    fn show_variables(&mut self, title: String, variables: Array<VarDictionary>) {
         let Some(np) = self.nvim_process.as_mut() else {
             return;
         };
-        np.var_respond(msgid, error, result);
+        debugger::show_variables(np, &title, &variables);
+    }
+
+    /// Connects neovim to the GDScript language server on `port` once it's reachable,
+    /// passing it along with `root_dir` in `g:vimdow_lsp`. Reconnects when neovim loses it.
+    /// A `port` of 0 tells neovim there's no language server
+    #[func]
+    fn set_language_server(&mut self, port: u16, root_dir: String) {
+        self.language_server.configure(port, root_dir);
+    }
+
+    /// Puts errors and warnings reported by godot in the quickfix list and `vim.diagnostic`,
+    /// replacing the previous ones of the same `source`. See `diagnostics::set_diagnostics`
+    #[func]
+    fn set_diagnostics(&mut self, source: String, items: Array<VarDictionary>) {
+        let Some(np) = self.nvim_process.as_mut() else {
+            return;
+        };
+        diagnostics::set_diagnostics(np, &source, &items);
+    }
+
+    /// Answers neovim's requests of `method` with `handler`, instead of emitting `neovim_request`.
+    /// `params` are the `Variant.Type`s of its parameters, `TYPE_NIL` accepting anything.
+    /// Requests with other arguments are answered with an error, otherwise the handler is
+    /// called with them and returns an array of [error, result].
+    /// Requests that have no handler and nothing connected to `neovim_request` are answered
+    /// with an error
+    #[func]
+    fn register_request_handler(&mut self, method: String, handler: Callable, params: VarArray) {
+        let mut types = vec![];
+        for (i, t) in params.iter_shared().enumerate() {
+            match t.try_to::<i32>() {
+                Ok(ord) if (0..VariantType::MAX.ord()).contains(&ord) => {
+                    types.push(VariantType::from_ord(ord));
+                }
+                _ => {
+                    godot_error!(
+                        "Didn't register {method}, the type of parameter {} is {t}, which isn't a Variant.Type",
+                        i + 1
+                    );
+                    return;
+                }
+            }
+        }
+        self.request_handlers
+            .insert(method, RequestHandler::script(handler, types));
+    }
+
+    /// Lets neovim control the editor, like playing scenes. See `handlers::EDITOR_METHODS`
+    #[func]
+    fn register_editor_methods(&mut self) {
+        for (method, params, handler) in EDITOR_METHODS {
+            self.request_handlers.insert(
+                method.to_string(),
+                RequestHandler::native(*handler, params.to_vec()),
+            );
+        }
+    }
+
+    #[func]
+    fn unregister_request_handler(&mut self, method: String) {
+        self.request_handlers.remove(&method);
+    }
+
+    #[func]
+    fn respond(&mut self, msgid: i32, error: Variant, result: Variant) {
+        let Ok(msgid) = u32::try_from(msgid) else {
+            godot_error!("Invalid msgid {msgid}");
+            return;
+        };
+        self.respond_variants(msgid, error, result);
     }
 
     #[func]
@@ -97,15 +467,21 @@ impl NeovimClient {
                 continue;
             }
 
-            let ni = NvimInput::from(event);
+            let ni = nvim_input(event);
             input.push_str(&ni.to_string());
         }
 
-        np.var_request("nvim_input", varray![&input.to_godot()]);
+        np.nvim_input(&input);
 
         inputs_buffer.clear();
     }
 
+    /// Should be kept in sync with neovim's 'mousemoveevent' option
+    #[func]
+    fn set_mouse_move_events(&mut self, enabled: bool) {
+        self.mouse.move_events = enabled;
+    }
+
     #[func]
     fn flush_mouse_inputs(
         &mut self,
@@ -118,7 +494,9 @@ impl NeovimClient {
         };
 
         for event in inputs_buffer.iter_shared() {
-            if let Some(nim) = NvimInputMouse::from_input_event(event, grid_index, cell_size) {
+            if let Some(nim) =
+                NvimInputMouse::from_input_event(event, &mut self.mouse, grid_index, cell_size)
+            {
                 nim.apply(np);
             }
         }
@@ -138,53 +516,110 @@ impl INode for NeovimClient {
             self.signals().neovim_quit().emit(e.code().unwrap_or(-1));
             return;
         }
+        self.language_server.poll(np);
 
         let mut messages = vec![];
-        while let Some(v) = np.check() {
-            if let Value::Array(rpc) = v {
-                messages.push(rpc);
-            } else {
-                godot_error!("not an array: {v:?}");
-            }
+        while let Some(message) = np.check() {
+            messages.push(message);
         }
 
-        for rpc in messages {
-            let msgtype = rpc.get(0).and_then(|v| v.as_u64()).unwrap_or(99);
-            match msgtype {
-                2 => {
-                    if let [Value::String(method), Value::Array(params)] = &rpc[1..3] {
-                        let params = rpc_array_to_vararray(params.clone());
-                        self.signals()
-                            .neovim_event()
-                            .emit(method.to_owned().into_str().unwrap(), &params);
+        // emitting through `self.signals()` goes through `base_mut()`, so the handlers
+        // can call back into the client, like the methods of a NeovimBuffer do
+        let ext = self.ext_types();
+        for message in messages {
+            match message {
+                Message::Notification { method, params } => {
+                    if self.handle_notification(&method, &params) {
+                        continue;
+                    }
+                    // the grids are drawn from here, the rest of the events are up to gdscript
+                    if method == "redraw" {
+                        for event in parse_redraw(&params) {
+                            self.ui.apply(&event);
+                        }
+                    }
+
+                    match rpc_array_to_vararray(params, &ext) {
+                        Ok(params) => self.signals().neovim_event().emit(method, &params),
+                        Err(e) => godot_error!("Dropped the {method} notification: {e}"),
                     }
                 }
-                1 => {
-                    if let [Value::Integer(msgid), error, result] = &rpc[1..4] {
-                        self.signals().neovim_response().emit(
-                            msgid.as_i64().unwrap() as i32,
-                            &rmpv_to_godot(error.to_owned()),
-                            &rmpv_to_godot(result.to_owned()),
-                        );
+                Message::Response {
+                    msgid,
+                    error,
+                    result,
+                } => {
+                    if self.api_info_msgid == Some(msgid) {
+                        self.api_info_msgid = None;
+                        self.read_api_info(&result);
+                        continue;
                     }
+                    // a batch that neovim ran is answered with its results, even when a call failed
+                    let reply = if self.batches.remove(&msgid) && error.is_nil() {
+                        batch_response(&result, &ext)
+                    } else {
+                        rmpv_to_godot(error, &ext)
+                            .and_then(|error| Ok((error, rmpv_to_godot(result, &ext)?)))
+                            .map_err(|e| e.to_string())
+                    };
+                    let (error, result) =
+                        reply.unwrap_or_else(|e| (e.to_variant(), Variant::nil()));
+                    self.signals()
+                        .neovim_response()
+                        .emit(msgid as i32, &error, &result);
                 }
-                0 => {
-                    if let [
-                        Value::Integer(msgid),
-                        Value::String(method),
-                        // Value::Array(params),
-                        // method,
-                        params,
-                    ] = &rpc[1..4]
-                    {
-                        self.signals().neovim_request().emit(
-                            msgid.as_i64().unwrap() as i32,
-                            method.to_string(),
-                            &rmpv_to_godot(params.to_owned()).to(),
-                        );
+                Message::Request {
+                    msgid,
+                    method,
+                    params,
+                } => {
+                    let has_listeners = !self
+                        .base()
+                        .get_signal_connection_list("neovim_request")
+                        .is_empty();
+                    match route(&method, &self.request_handlers, has_listeners) {
+                        // some requests are serviced natively instead of through gdscript
+                        Route::Clipboard => {
+                            if let Some(response) = self.clipboard.handle(&method, &params)
+                                && let Some(np) = self.nvim_process.as_mut()
+                            {
+                                let result = match response {
+                                    Ok(result) => np.respond(msgid, Value::Nil, result),
+                                    Err(error) => np.respond(msgid, error, Value::Nil),
+                                };
+                                if let Err(e) = result {
+                                    godot_error!("{e}");
+                                }
+                            }
+                            continue;
+                        }
+                        Route::Handler => {
+                            let handler = self.request_handlers[&method].clone();
+                            self.call_request_handler(msgid, &method, &handler, &params);
+                            continue;
+                        }
+                        Route::Reject => {
+                            if let Some(np) = self.nvim_process.as_mut()
+                                && let Err(e) = np.reject(msgid, &method)
+                            {
+                                godot_error!("{e}");
+                            }
+                            continue;
+                        }
+                        Route::Listeners => {}
+                    }
+
+                    match rpc_array_to_vararray(params, &ext) {
+                        Ok(params) => {
+                            self.signals()
+                                .neovim_request()
+                                .emit(msgid as i32, method, &params)
+                        }
+                        Err(e) => {
+                            self.respond_variants(msgid, e.to_string().to_variant(), Variant::nil())
+                        }
                     }
                 }
-                _ => godot_error!("Got a non-existent message type: {msgtype}"),
             }
         }
     }
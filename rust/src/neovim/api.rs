@@ -0,0 +1,46 @@
+//! Typed bindings of neovim's API, generated by `build.rs` from `api/api-info.msgpack`.
+//! `NvimApi` has a method for each API function, and `NeovimClient` a #[func] that checks
+//! that the running neovim has it first. Both return the msgid of the response
+
+use godot::prelude::*;
+use rmpv::Value;
+use vimdow_core::process::NeovimProcess;
+
+use crate::neovim::NeovimClient;
+use crate::neovim::ext_types::{Buffer, Tabpage, Window};
+use crate::neovim::msgpack::{ConversionError, dict_to_rpc, godot_to_rmpv, vararray_to_rpc};
+
+include!(concat!(env!("OUT_DIR"), "/nvim_api.rs"));
+
+// an argument of a #[func], or None once it's logged why it can't be sent
+fn argument<T>(method: &str, converted: Result<T, ConversionError>) -> Option<T> {
+    converted
+        .inspect_err(|e| godot_error!("Can't call {method}: {e}"))
+        .ok()
+}
+
+impl NeovimClient {
+    // the process to call a function with, unless the running neovim is too old to have it
+    pub fn api_process(
+        &mut self,
+        method: &str,
+        since: u64,
+        deprecated_since: Option<u64>,
+    ) -> Option<&mut NeovimProcess> {
+        if let Some(d) = deprecated_since
+            && self.deprecated_calls.insert(method.to_string())
+        {
+            godot_warn!("{method} is deprecated since API level {d}");
+        }
+        if let Some(info) = &self.api_info
+            && info.api_level < since
+        {
+            godot_error!(
+                "{method} needs API level {since}, neovim only has {}",
+                info.api_level
+            );
+            return None;
+        }
+        self.nvim_process.as_mut()
+    }
+}
@@ -0,0 +1,82 @@
+use godot::prelude::*;
+use rmpv::Value;
+use vimdow_core::batch::{Batch, BatchResult};
+
+use crate::neovim::NeovimClient;
+use crate::neovim::ext_types::ExtTypes;
+use crate::neovim::msgpack::{rpc_array_to_vararray, vararray_to_rpc};
+
+/// API calls collected by `NeovimClient.batch()`, which are sent as one `nvim_call_atomic`
+/// request. Its `neovim_response` has the results of the calls that ran, and an error of
+/// `{ index, type, message }` when one of them failed
+#[derive(GodotClass)]
+#[class(tool, no_init, base=RefCounted)]
+pub struct NeovimBatch {
+    base: Base<RefCounted>,
+    client: Gd<NeovimClient>,
+    batch: Batch,
+    // set by a call whose params can't be sent, which would shift the index of a failure
+    error: Option<String>,
+}
+
+impl NeovimBatch {
+    pub fn new(client: Gd<NeovimClient>) -> Gd<Self> {
+        Gd::from_init_fn(|base| Self {
+            base,
+            client,
+            batch: Batch::new(),
+            error: None,
+        })
+    }
+}
+
+#[godot_api]
+impl NeovimBatch {
+    /// Adds a call to the batch, and returns the batch to chain more
+    #[func]
+    fn add(&mut self, method: String, params: VarArray) -> Gd<Self> {
+        match vararray_to_rpc(params) {
+            Ok(params) => {
+                self.batch.add(&method, params);
+            }
+            Err(e) => {
+                self.error
+                    .get_or_insert_with(|| format!("Can't call {method}: {e}"));
+            }
+        }
+        self.to_gd()
+    }
+
+    #[func]
+    fn size(&self) -> i32 {
+        self.batch.len() as i32
+    }
+
+    /// Sends the calls added so far, returns the msgid of the request or -1
+    #[func]
+    fn send(&mut self) -> i32 {
+        if let Some(e) = self.error.take() {
+            godot_error!("Batch wasn't sent. {e}");
+            return -1;
+        }
+        let batch = std::mem::take(&mut self.batch);
+        self.client.bind_mut().send_batch(batch)
+    }
+}
+
+/// Splits the result of `nvim_call_atomic` into the error of the call that failed and the
+/// results of the ones before it
+pub fn batch_response(result: &Value, ext: &ExtTypes) -> Result<(Variant, Variant), String> {
+    let BatchResult { results, error } = BatchResult::parse(result).map_err(|e| e.to_string())?;
+    let results = rpc_array_to_vararray(results, ext).map_err(|e| e.to_string())?;
+    let error = match error {
+        Some(e) => vdict! {
+            "index" => e.index as i64,
+            "type" => e.kind,
+            "message" => e.message,
+        }
+        .to_variant(),
+        None => Variant::nil(),
+    };
+    Ok((error, results.to_variant()))
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use rmpv::Value;
+use ropey::Rope;
+
+use crate::neovim::ext_types::ext_handle;
+
+struct SyncedBuffer {
+    path: String,
+    // every line ends with a newline, so the buffer's line `n` is the rope's line `n`
+    text: Rope,
+}
+
+/// Live copies of the buffers attached with `nvim_buf_attach`, see ":h api-buffer-updates"
+#[derive(Default)]
+pub struct Buffers {
+    synced: HashMap<i64, SyncedBuffer>,
+}
+
+impl Buffers {
+    pub fn track(&mut self, handle: i64, path: String) {
+        self.synced.insert(
+            handle,
+            SyncedBuffer {
+                path,
+                text: Rope::new(),
+            },
+        );
+    }
+
+    /// Applies the params of an `nvim_buf_lines_event`, returning the path of the changed buffer
+    pub fn apply_lines_event(&mut self, params: &[Value]) -> Option<&str> {
+        let [buf, _changedtick, first, last, Value::Array(lines), ..] = params else {
+            return None;
+        };
+        let synced = self.synced.get_mut(&ext_handle(buf)?)?;
+        let text = &mut synced.text;
+
+        let line_count = text.len_lines() - 1;
+        let first = (first.as_u64()? as usize).min(line_count);
+        // -1 means the end of the buffer
+        let last = match last.as_i64()? {
+            -1 => line_count,
+            l => (l as usize).clamp(first, line_count),
+        };
+
+        let start = text.line_to_char(first);
+        let end = text.line_to_char(last);
+        text.remove(start..end);
+
+        let mut replacement = String::new();
+        for line in lines {
+            replacement.push_str(line.as_str().unwrap_or_default());
+            replacement.push('\n');
+        }
+        text.insert(start, &replacement);
+
+        Some(&synced.path)
+    }
+
+    /// Stops tracking a buffer after an `nvim_buf_detach_event`, returning its path
+    pub fn detach(&mut self, params: &[Value]) -> Option<String> {
+        let handle = ext_handle(params.first()?)?;
+        self.synced.remove(&handle).map(|b| b.path)
+    }
+
+    pub fn text(&self, path: &str) -> Option<String> {
+        self.synced
+            .values()
+            .find(|b| b.path == path)
+            .map(|b| b.text.to_string())
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use godot::classes::DisplayServer;
+use godot::classes::display_server::Feature;
+use godot::obj::Singleton;
+use rmpv::Value;
+
+/// Lua that points `g:clipboard` at the client, so "+ and "* go through godot
+/// instead of needing xclip/wl-copy on the user's system
+pub const PROVIDER_LUA: &str = r#"
+-- godot started neovim with --embed, so its channel is the rpc one on stdio
+if not vim.g.vimdow_channel then
+	for _, chan in ipairs(vim.api.nvim_list_chans()) do
+		if chan.mode == "rpc" and chan.stream == "stdio" then
+			vim.g.vimdow_channel = chan.id
+		end
+	end
+end
+
+local function copy (reg)
+	return function (lines, regtype)
+		vim.rpcrequest(vim.g.vimdow_channel, "vimdow_clipboard_set", reg, lines, regtype)
+	end
+end
+
+local function paste (reg)
+	return function ()
+		return vim.rpcrequest(vim.g.vimdow_channel, "vimdow_clipboard_get", reg)
+	end
+end
+
+vim.g.clipboard = {
+	name = "vimdow",
+	copy = { ["+"] = copy "+", ["*"] = copy "*" },
+	paste = { ["+"] = paste "+", ["*"] = paste "*" },
+	cache_enabled = false,
+}
+
+-- the provider may have been loaded already by the user's config
+vim.g.loaded_clipboard_provider = nil
+vim.cmd "runtime autoload/provider/clipboard.vim"
+"#;
+
+/// Services neovim's clipboard provider with godot's `DisplayServer`
+#[derive(Default)]
+pub struct Clipboard {
+    // what was last yanked into each register, so that the
+    // regtype survives a round trip through the system clipboard
+    yanked: HashMap<String, (String, Value)>,
+}
+
+impl Clipboard {
+    /// Handles requests made by [`PROVIDER_LUA`], returns `None` for every other method
+    pub fn handle(&mut self, method: &str, params: &[Value]) -> Option<Result<Value, Value>> {
+        let register = params.first().and_then(Value::as_str).unwrap_or("+");
+        match method {
+            "vimdow_clipboard_set" => {
+                let (Some(Value::Array(lines)), Some(regtype)) = (params.get(1), params.get(2))
+                else {
+                    return Some(Err("expected (register, lines, regtype)".into()));
+                };
+                let mut text = lines
+                    .iter()
+                    .map(|l| l.as_str().unwrap_or_default())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if regtype.as_str() == Some("V") {
+                    text.push('\n');
+                }
+
+                Self::set_text(register, &text);
+                self.yanked
+                    .insert(register.to_string(), (text, regtype.clone()));
+                Some(Ok(Value::Nil))
+            }
+            "vimdow_clipboard_get" => {
+                let text = Self::get_text(register);
+                let regtype = match self.yanked.get(register) {
+                    Some((yanked, regtype)) if *yanked == text => regtype.clone(),
+                    _ if text.ends_with('\n') => "V".into(),
+                    _ => "v".into(),
+                };
+
+                let mut lines: Vec<Value> = text.split('\n').map(Value::from).collect();
+                if text.ends_with('\n') {
+                    lines.pop();
+                }
+                Some(Ok(Value::Array(vec![Value::Array(lines), regtype])))
+            }
+            _ => None,
+        }
+    }
+
+    // "* is the primary selection wherever godot supports one
+    fn uses_primary(register: &str) -> bool {
+        register == "*" && DisplayServer::singleton().has_feature(Feature::CLIPBOARD_PRIMARY)
+    }
+
+    fn set_text(register: &str, text: &str) {
+        let mut ds = DisplayServer::singleton();
+        if Self::uses_primary(register) {
+            ds.clipboard_set_primary(text);
+        } else {
+            ds.clipboard_set(text);
+        }
+    }
+
+    fn get_text(register: &str) -> String {
+        let ds = DisplayServer::singleton();
+        if Self::uses_primary(register) {
+            ds.clipboard_get_primary()
+        } else {
+            ds.clipboard_get()
+        }
+        .to_string()
+    }
+}
@@ -0,0 +1,191 @@
+use godot::classes::ProjectSettings;
+use godot::global::type_string;
+use godot::prelude::*;
+use rmpv::Value;
+
+use crate::neovim::api::NvimApi;
+use vimdow_core::process::NeovimProcess;
+
+// marks the line execution stopped on, in whatever buffer it's in, and puts the stack in
+// the quickfix list, replacing the previous stack but not a list of the user's
+const SHOW_BREAK_LUA: &str = r#"
+local path, line, items = ...
+local ns = vim.api.nvim_create_namespace "vimdow_break"
+vim.api.nvim_set_hl(0, "VimdowBreakLine", { link = "CursorLine", default = true })
+vim.api.nvim_set_hl(0, "VimdowBreakSign", { link = "DiagnosticWarn", default = true })
+
+for _, buf in ipairs(vim.api.nvim_list_bufs()) do
+	vim.api.nvim_buf_clear_namespace(buf, ns, 0, -1)
+end
+
+vim.cmd.edit(vim.fn.fnameescape(path))
+line = math.min(line, vim.api.nvim_buf_line_count(0))
+vim.api.nvim_win_set_cursor(0, { line, 0 })
+vim.api.nvim_buf_set_extmark(0, ns, line - 1, 0, {
+	sign_text = "▶",
+	sign_hl_group = "VimdowBreakSign",
+	line_hl_group = "VimdowBreakLine",
+	priority = 50,
+})
+
+local title = "Godot stack"
+local current = vim.fn.getqflist { title = 0 }
+vim.fn.setqflist({}, current.title == title and "r" or " ", { title = title, items = items })
+"#;
+
+const CLEAR_BREAK_LUA: &str = r#"
+local ns = vim.api.nvim_create_namespace "vimdow_break"
+for _, buf in ipairs(vim.api.nvim_list_bufs()) do
+	vim.api.nvim_buf_clear_namespace(buf, ns, 0, -1)
+end
+"#;
+
+// limits that keep huge containers from flooding the inspector
+const MAX_DEPTH: usize = 8;
+const MAX_CHILDREN: usize = 200;
+const MAX_VALUE_LEN: usize = 200;
+
+// scopes of the variables of a stack frame, as numbered by godot's debugger
+const SCOPES: [&str; 3] = ["Locals", "Members", "Globals"];
+
+/// A frame of a paused script's stack, as sent by godot's debugger
+struct StackFrame {
+    path: String,
+    line: i64,
+    function: String,
+}
+
+impl StackFrame {
+    fn from_dict(frame: &VarDictionary) -> Option<Self> {
+        let file = frame.get("file")?.try_to::<GString>().ok()?;
+        Some(Self {
+            path: ProjectSettings::singleton()
+                .globalize_path(&file)
+                .to_string(),
+            line: frame.get("line")?.try_to().ok()?,
+            function: frame
+                .get("function")
+                .map(|f| f.to_string())
+                .unwrap_or_default(),
+        })
+    }
+
+    fn to_qf_item(&self) -> Value {
+        Value::Map(vec![
+            ("filename".into(), self.path.as_str().into()),
+            ("lnum".into(), self.line.into()),
+            ("text".into(), self.function.as_str().into()),
+        ])
+    }
+}
+
+/// Shows where execution stopped and fills the quickfix list with the stack.
+/// `frames` are dictionaries of `file`, `line` and `function`, innermost first
+pub fn show_break(np: &mut NeovimProcess, frames: &Array<VarDictionary>) {
+    let frames: Vec<StackFrame> = frames
+        .iter_shared()
+        .filter_map(|f| StackFrame::from_dict(&f))
+        .collect();
+    let Some(top) = frames.first() else {
+        return;
+    };
+
+    let items = Value::Array(frames.iter().map(StackFrame::to_qf_item).collect());
+    np.nvim_exec_lua(
+        SHOW_BREAK_LUA,
+        vec![top.path.as_str().into(), top.line.into(), items],
+    );
+}
+
+pub fn clear_break(np: &mut NeovimProcess) {
+    np.nvim_exec_lua(CLEAR_BREAK_LUA, vec![]);
+}
+
+// a node of the tree rendered by `vimdow.inspect`
+fn tree_node(name: String, value: &Variant, depth: usize) -> Value {
+    let mut children = vec![];
+    let summary = match value.get_type() {
+        VariantType::ARRAY => {
+            let arr: VarArray = value.to();
+            if depth < MAX_DEPTH {
+                children = arr
+                    .iter_shared()
+                    .take(MAX_CHILDREN)
+                    .enumerate()
+                    .map(|(i, v)| tree_node(format!("[{i}]"), &v, depth + 1))
+                    .collect();
+            }
+            format!("size {}", arr.len())
+        }
+        VariantType::DICTIONARY => {
+            let dict: VarDictionary = value.to();
+            if depth < MAX_DEPTH {
+                children = dict
+                    .iter_shared()
+                    .take(MAX_CHILDREN)
+                    .map(|(k, v)| tree_node(k.stringify().to_string(), &v, depth + 1))
+                    .collect();
+            }
+            format!("size {}", dict.len())
+        }
+        _ => {
+            let s = value.stringify().to_string();
+            match s.char_indices().nth(MAX_VALUE_LEN) {
+                Some((i, _)) => format!("{}…", &s[..i]),
+                None => s,
+            }
+        }
+    };
+
+    Value::Map(vec![
+        ("name".into(), name.into()),
+        (
+            "type".into(),
+            type_string(value.get_type().ord() as i64)
+                .to_string()
+                .into(),
+        ),
+        ("value".into(), summary.into()),
+        ("children".into(), Value::Array(children)),
+    ])
+}
+
+fn group_node(name: &str, children: Vec<Value>, expanded: bool) -> Value {
+    Value::Map(vec![
+        ("name".into(), name.into()),
+        ("type".into(), "".into()),
+        (
+            "value".into(),
+            format!("{} variables", children.len()).into(),
+        ),
+        ("children".into(), Value::Array(children)),
+        ("expanded".into(), expanded.into()),
+    ])
+}
+
+/// Opens a floating window with an expandable tree of the variables of a paused game.
+/// `variables` are dictionaries of `name`, `value` and optionally the `scope` godot reported
+pub fn show_variables(np: &mut NeovimProcess, title: &str, variables: &Array<VarDictionary>) {
+    let mut scoped: [Vec<Value>; 3] = Default::default();
+    let mut unscoped = vec![];
+    for var in variables.iter_shared() {
+        let name = var.get("name").map(|n| n.to_string()).unwrap_or_default();
+        let node = tree_node(name, &var.get_or_nil("value"), 0);
+        match var.get("scope").and_then(|s| s.try_to::<u32>().ok()) {
+            Some(scope) if (scope as usize) < SCOPES.len() => scoped[scope as usize].push(node),
+            _ => unscoped.push(node),
+        }
+    }
+
+    let mut tree = unscoped;
+    for (i, (name, children)) in SCOPES.iter().zip(scoped).enumerate() {
+        if !children.is_empty() {
+            tree.push(group_node(name, children, i == 0));
+        }
+    }
+
+    np.nvim_exec_lua(
+        "require(\"vimdow.inspect\").open(...)",
+        vec![title.into(), Value::Array(tree)],
+    );
+}
@@ -0,0 +1,85 @@
+use godot::classes::ProjectSettings;
+use godot::prelude::*;
+use rmpv::Value;
+
+use crate::neovim::api::NvimApi;
+use vimdow_core::process::NeovimProcess;
+
+// sets the diagnostics of a source in every buffer they point at,
+// and replaces the source's quickfix list if it's the current one
+const SET_DIAGNOSTICS_LUA: &str = r#"
+local source, items = ...
+local ns = vim.api.nvim_create_namespace("vimdow_" .. source)
+vim.diagnostic.reset(ns)
+
+local by_buf = {}
+for _, item in ipairs(items) do
+	local buf = vim.fn.bufadd(item.filename)
+	by_buf[buf] = by_buf[buf] or {}
+	table.insert(by_buf[buf], {
+		lnum = math.max(item.lnum - 1, 0),
+		col = 0,
+		message = item.text,
+		severity = item.type == "W" and vim.diagnostic.severity.WARN or vim.diagnostic.severity.ERROR,
+		source = "godot",
+	})
+end
+for buf, diagnostics in pairs(by_buf) do
+	vim.diagnostic.set(ns, buf, diagnostics)
+end
+
+local title = "Godot " .. source
+local current = vim.fn.getqflist { title = 0 }
+vim.fn.setqflist({}, current.title == title and "r" or " ", { title = title, items = items })
+"#;
+
+/// An error or warning godot reported about a line of a project file
+struct Diagnostic {
+    path: String,
+    line: i64,
+    message: String,
+    warning: bool,
+}
+
+impl Diagnostic {
+    fn from_dict(item: &VarDictionary) -> Option<Self> {
+        let file = item.get("file")?.try_to::<GString>().ok()?;
+        Some(Self {
+            path: ProjectSettings::singleton()
+                .globalize_path(&file)
+                .to_string(),
+            line: item.get("line")?.try_to().ok()?,
+            message: item
+                .get("message")
+                .map(|m| m.to_string())
+                .unwrap_or_default(),
+            warning: item
+                .get("severity")
+                .is_some_and(|s| s.to_string() == "warning"),
+        })
+    }
+
+    fn to_qf_item(&self) -> Value {
+        Value::Map(vec![
+            ("filename".into(), self.path.as_str().into()),
+            ("lnum".into(), self.line.into()),
+            ("text".into(), self.message.as_str().into()),
+            ("type".into(), if self.warning { "W" } else { "E" }.into()),
+        ])
+    }
+}
+
+/// Replaces the diagnostics and quickfix list of `source`. `items` are dictionaries
+/// of `file`, `line`, `message` and a `severity` of either "error" or "warning"
+pub fn set_diagnostics(np: &mut NeovimProcess, source: &str, items: &Array<VarDictionary>) {
+    let items: Vec<Value> = items
+        .iter_shared()
+        .filter_map(|i| Diagnostic::from_dict(&i))
+        .map(|d| d.to_qf_item())
+        .collect();
+
+    np.nvim_exec_lua(
+        SET_DIAGNOSTICS_LUA,
+        vec![source.into(), Value::Array(items)],
+    );
+}
@@ -1,28 +1,281 @@
+//! Neovim's Buffer, Window and Tabpage handles, which msgpack-rpc sends as ext types.
+//! Their ids come from the `types` table of the api-info, see ":h api-metadata"
+
 use godot::prelude::*;
-// Neovim Ext Types
-
-#[derive(GodotConvert, Default, Clone, Var, Export)]
-#[godot(transparent)]
-pub struct Window(i64);
-
-#[derive(GodotConvert, Default, Clone, Var, Export)]
-#[godot(transparent)]
-pub struct Buffer(i64);
-
-#[derive(GodotConvert, Default, Clone, Var, Export)]
-#[godot(transparent)]
-pub struct Tabpage(i64);
-
-pub fn rmpv_ext_to_godot(t: i8, data: Vec<u8>) -> Variant {
-    let decoded = rmpv::decode::read_value(&mut data.as_slice()).map(|d| d.as_u64().unwrap_or(99));
-    if let Ok(handle) = decoded {
-        match t {
-            0 => Buffer(handle as i64).to_variant(),
-            1 => Window(handle as i64).to_variant(),
-            2 => Tabpage(handle as i64).to_variant(),
-            _ => Variant::nil(),
+use rmpv::Value;
+use vimdow_core::api_info::{ApiInfo, Handle};
+
+use crate::neovim::NeovimClient;
+use crate::neovim::api::EXT_TYPES;
+use crate::neovim::msgpack::ConversionError;
+
+/// Decodes the handles neovim sends, giving them the client their methods call
+#[derive(Clone)]
+pub struct ExtTypes {
+    ids: Vec<(String, i8)>,
+    client: Option<Gd<NeovimClient>>,
+}
+
+impl ExtTypes {
+    /// Uses the ids of the checked-in api-info until neovim sent its own
+    pub fn new(info: Option<&ApiInfo>, client: Option<Gd<NeovimClient>>) -> Self {
+        let ids = match info {
+            Some(info) => info.types.iter().map(|t| (t.name.clone(), t.id)).collect(),
+            None => EXT_TYPES
+                .iter()
+                .map(|&(name, id)| (name.to_string(), id))
+                .collect(),
+        };
+        Self { ids, client }
+    }
+
+    /// The handle of an object of the type called `name`, like "Buffer"
+    pub fn handle(&self, name: &str, id: i64) -> Option<Handle> {
+        let (_, ext) = self.ids.iter().find(|(n, _)| n == name)?;
+        Some(Handle { ext: *ext, id })
+    }
+
+    /// Handles for the ext types of the api-info, and the data of other ones as bytes
+    pub fn decode(&self, ext: i8, data: &[u8]) -> Result<Variant, ConversionError> {
+        let Some((name, _)) = self.ids.iter().find(|(_, id)| *id == ext) else {
+            return Ok(unknown(ext, data));
+        };
+        let handle = Handle::decode(ext, data).ok_or(ConversionError::InvalidExt(ext))?;
+        let client = self.client.clone();
+        Ok(match name.as_str() {
+            "Buffer" => Buffer::new(handle, client).to_variant(),
+            "Window" => Window::new(handle, client).to_variant(),
+            "Tabpage" => Tabpage::new(handle, client).to_variant(),
+            // a type added by a newer neovim
+            _ => unknown(ext, data),
+        })
+    }
+}
+
+// kept as bytes instead of failing the whole message it's in
+fn unknown(ext: i8, data: &[u8]) -> Variant {
+    godot_warn!("Neovim sent the unknown ext type {ext}, passing its data on as bytes");
+    PackedByteArray::from(data).to_variant()
+}
+
+macro_rules! handle_type {
+    ($t:ident) => {
+        impl $t {
+            pub fn new(handle: Handle, client: Option<Gd<NeovimClient>>) -> Gd<Self> {
+                Gd::from_init_fn(|base| Self {
+                    base,
+                    handle,
+                    client,
+                })
+            }
+
+            // the client that decoded the handle, if it's still around
+            fn client(&self) -> Option<Gd<NeovimClient>> {
+                let client = self.client.clone().filter(|c| c.is_instance_valid());
+                if client.is_none() {
+                    godot_error!(
+                        "{} {} has no neovim to call",
+                        stringify!($t),
+                        self.handle.id
+                    );
+                }
+                client
+            }
+        }
+
+        impl From<&$t> for Value {
+            fn from(handle: &$t) -> Self {
+                handle.handle.into()
+            }
         }
-    } else {
-        Variant::nil()
+    };
+}
+
+/// A neovim buffer. Its methods return the msgid of their `neovim_response`
+#[derive(GodotClass)]
+#[class(tool, no_init, rename = NeovimBuffer, base = RefCounted)]
+pub struct Buffer {
+    base: Base<RefCounted>,
+    handle: Handle,
+    client: Option<Gd<NeovimClient>>,
+}
+
+handle_type!(Buffer);
+
+#[godot_api]
+impl Buffer {
+    #[func]
+    fn get_id(&self) -> i64 {
+        self.handle.id
+    }
+
+    #[func]
+    fn is_valid(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_buf_is_valid(self.to_gd())
+    }
+
+    /// The lines from `start` up to `end`, which are zero-based. Negative indices count
+    /// from the last line
+    #[func]
+    fn get_lines(&self, start: i64, end: i64) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client
+            .bind_mut()
+            .nvim_buf_get_lines(self.to_gd(), start, end, false)
+    }
+
+    /// Replaces the lines from `start` up to `end`, indexed like `get_lines`
+    #[func]
+    fn set_lines(&self, start: i64, end: i64, lines: PackedStringArray) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        let lines = lines.as_slice().iter().map(|l| l.to_variant()).collect();
+        client
+            .bind_mut()
+            .nvim_buf_set_lines(self.to_gd(), start, end, false, lines)
+    }
+
+    #[func]
+    fn line_count(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_buf_line_count(self.to_gd())
+    }
+
+    #[func]
+    fn get_name(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_buf_get_name(self.to_gd())
+    }
+}
+
+/// A neovim window. Its methods return the msgid of their `neovim_response`
+#[derive(GodotClass)]
+#[class(tool, no_init, rename = NeovimWindow, base = RefCounted)]
+pub struct Window {
+    base: Base<RefCounted>,
+    handle: Handle,
+    client: Option<Gd<NeovimClient>>,
+}
+
+handle_type!(Window);
+
+#[godot_api]
+impl Window {
+    #[func]
+    fn get_id(&self) -> i64 {
+        self.handle.id
+    }
+
+    #[func]
+    fn is_valid(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_win_is_valid(self.to_gd())
+    }
+
+    #[func]
+    fn get_buf(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_win_get_buf(self.to_gd())
+    }
+
+    #[func]
+    fn set_buf(&self, buffer: Gd<Buffer>) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_win_set_buf(self.to_gd(), buffer)
+    }
+
+    /// The cursor as `[row, col]`, the row starting at 1 and the column at 0
+    #[func]
+    fn get_cursor(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_win_get_cursor(self.to_gd())
+    }
+
+    #[func]
+    fn set_cursor(&self, row: i64, col: i64) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client
+            .bind_mut()
+            .nvim_win_set_cursor(self.to_gd(), varray![row, col])
+    }
+
+    #[func]
+    fn get_tabpage(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_win_get_tabpage(self.to_gd())
+    }
+}
+
+/// A neovim tabpage. Its methods return the msgid of their `neovim_response`
+#[derive(GodotClass)]
+#[class(tool, no_init, rename = NeovimTabpage, base = RefCounted)]
+pub struct Tabpage {
+    base: Base<RefCounted>,
+    handle: Handle,
+    client: Option<Gd<NeovimClient>>,
+}
+
+handle_type!(Tabpage);
+
+#[godot_api]
+impl Tabpage {
+    #[func]
+    fn get_id(&self) -> i64 {
+        self.handle.id
+    }
+
+    #[func]
+    fn is_valid(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_tabpage_is_valid(self.to_gd())
+    }
+
+    #[func]
+    fn get_win(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_tabpage_get_win(self.to_gd())
+    }
+
+    #[func]
+    fn list_wins(&self) -> i32 {
+        let Some(mut client) = self.client() else {
+            return -1;
+        };
+        client.bind_mut().nvim_tabpage_list_wins(self.to_gd())
+    }
+}
+
+/// Handle of a Buffer/Window/Tabpage, whether it was sent as an ext type or a plain integer
+pub fn ext_handle(v: &Value) -> Option<i64> {
+    match v {
+        Value::Integer(i) => i.as_i64(),
+        Value::Ext(ext, data) => Handle::decode(*ext, data).map(|h| h.id),
+        _ => None,
     }
 }
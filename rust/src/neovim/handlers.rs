@@ -0,0 +1,182 @@
+use godot::classes::{EditorInterface, ProjectSettings, ResourceLoader};
+use godot::global::type_string;
+use godot::prelude::*;
+
+/// Answers a request natively, with arguments that already match its parameters
+pub type NativeHandler = fn(&VarArray) -> Result<Variant, String>;
+
+#[derive(Clone)]
+enum Call {
+    // answers with an array of [error, result]
+    Script(Callable),
+    Native(NativeHandler),
+}
+
+/// A method neovim can call, see `NeovimClient::register_request_handler`
+#[derive(Clone)]
+pub struct RequestHandler {
+    call: Call,
+    // NIL accepts any type
+    params: Vec<VariantType>,
+}
+
+fn type_name(ty: VariantType) -> String {
+    type_string(ty.ord() as i64).to_string()
+}
+
+// converts an argument to the parameter's type when nothing is lost,
+// which lets commands pass numbers as strings
+fn coerce(arg: Variant, ty: VariantType) -> Option<Variant> {
+    let arg_ty = arg.get_type();
+    if ty == VariantType::NIL || arg_ty == ty {
+        return Some(arg);
+    }
+
+    match (arg_ty, ty) {
+        (VariantType::INT, VariantType::FLOAT) => Some((arg.to::<i64>() as f64).to_variant()),
+        (VariantType::STRING, VariantType::INT) => arg
+            .to::<GString>()
+            .to_string()
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .map(|i| i.to_variant()),
+        (VariantType::STRING, VariantType::FLOAT) => arg
+            .to::<GString>()
+            .to_string()
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .map(|f| f.to_variant()),
+        (VariantType::STRING, VariantType::BOOL) => match arg.to::<GString>().to_string().trim() {
+            "true" | "1" => Some(true.to_variant()),
+            "false" | "0" => Some(false.to_variant()),
+            _ => None,
+        },
+        (VariantType::STRING, VariantType::STRING_NAME) => {
+            Some(StringName::from(&arg.to::<GString>()).to_variant())
+        }
+        (VariantType::STRING, VariantType::NODE_PATH) => {
+            Some(NodePath::from(&arg.to::<GString>()).to_variant())
+        }
+        (VariantType::ARRAY, VariantType::PACKED_STRING_ARRAY) => {
+            let arr = arg.to::<VarArray>();
+            let strings: Option<PackedStringArray> = arr
+                .iter_shared()
+                .map(|v| v.try_to::<GString>().ok())
+                .collect();
+            strings.map(|s| s.to_variant())
+        }
+        _ => None,
+    }
+}
+
+impl RequestHandler {
+    pub fn script(callable: Callable, params: Vec<VariantType>) -> Self {
+        Self {
+            call: Call::Script(callable),
+            params,
+        }
+    }
+
+    pub fn native(handler: NativeHandler, params: Vec<VariantType>) -> Self {
+        Self {
+            call: Call::Native(handler),
+            params,
+        }
+    }
+
+    /// Checks the arguments of a request against the handler's parameters
+    pub fn check_args(&self, method: &str, args: VarArray) -> Result<VarArray, String> {
+        if args.len() != self.params.len() {
+            return Err(format!(
+                "{method} takes {} argument(s), got {}",
+                self.params.len(),
+                args.len()
+            ));
+        }
+
+        args.iter_shared()
+            .zip(&self.params)
+            .enumerate()
+            .map(|(i, (arg, ty))| {
+                let arg_ty = arg.get_type();
+                coerce(arg, *ty).ok_or_else(|| {
+                    format!(
+                        "argument {} of {method} must be {}, got {}",
+                        i + 1,
+                        type_name(*ty),
+                        type_name(arg_ty)
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Runs the handler, which can call back into the client
+    pub fn call(&self, args: &VarArray) -> Result<Variant, Variant> {
+        match &self.call {
+            Call::Native(handler) => handler(args).map_err(|e| e.to_variant()),
+            Call::Script(callable) => {
+                let reply = callable.callv(args);
+                match reply.try_to::<VarArray>() {
+                    Ok(reply) if reply.len() == 2 => {
+                        let error = reply.at(0);
+                        if error.is_nil() {
+                            Ok(reply.at(1))
+                        } else {
+                            Err(error)
+                        }
+                    }
+                    _ => Err("request handlers must return [error, result]".to_variant()),
+                }
+            }
+        }
+    }
+}
+
+fn resource_path(args: &VarArray) -> Result<GString, String> {
+    let path = ProjectSettings::singleton().localize_path(&args.at(0).to::<GString>());
+    if ResourceLoader::singleton().exists(&path) {
+        Ok(path)
+    } else {
+        Err(format!("No resource at '{path}'"))
+    }
+}
+
+/// Editor actions neovim can call, registered by `NeovimClient::register_editor_methods`
+pub const EDITOR_METHODS: &[(&str, &[VariantType], NativeHandler)] = &[
+    ("play_main_scene", &[], |_| {
+        EditorInterface::singleton().play_main_scene();
+        Ok(Variant::nil())
+    }),
+    ("play_current_scene", &[], |_| {
+        EditorInterface::singleton().play_current_scene();
+        Ok(Variant::nil())
+    }),
+    ("play_custom_scene", &[VariantType::STRING], |args| {
+        let path = resource_path(args)?;
+        EditorInterface::singleton().play_custom_scene(&path);
+        Ok(Variant::nil())
+    }),
+    ("stop_playing_scene", &[], |_| {
+        EditorInterface::singleton().stop_playing_scene();
+        Ok(Variant::nil())
+    }),
+    ("inspect_resource", &[VariantType::STRING], |args| {
+        let path = resource_path(args)?;
+        let res = ResourceLoader::singleton()
+            .load(&path)
+            .ok_or_else(|| format!("Couldn't load '{path}'"))?;
+        EditorInterface::singleton().inspect_object(&res);
+        Ok(Variant::nil())
+    }),
+    ("reimport_file", &[VariantType::STRING], |args| {
+        let path = resource_path(args)?;
+        let mut fs = EditorInterface::singleton()
+            .get_resource_filesystem()
+            .ok_or("The editor's filesystem isn't available")?;
+        fs.reimport_files(&PackedStringArray::from([path]));
+        Ok(Variant::nil())
+    }),
+];
@@ -0,0 +1,339 @@
+//! Tests that need the engine, Variants can't be made without it. They're built with the
+//! `itest` feature and run by `godot/tests/run_itests.gd`, see the README
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use godot::classes::{INode, Node};
+use godot::prelude::*;
+use rmpv::Value;
+use vimdow_core::api_info::Handle;
+use vimdow_core::rpc::Message;
+
+use crate::neovim::NeovimClient;
+use crate::neovim::api::EXT_TYPES;
+use crate::neovim::ext_types::ExtTypes;
+use crate::neovim::msgpack::{
+    ConversionError, godot_to_rmpv, rmpv_to_godot, rpc_array_to_vararray,
+};
+
+/// Runs the in-engine tests of vimdow
+#[derive(GodotClass)]
+#[class(tool, init, base = RefCounted)]
+pub struct VimdowTests {
+    base: Base<RefCounted>,
+}
+
+const TESTS: &[(&str, fn())] = &[
+    (
+        "variants_survive_a_round_trip",
+        variants_survive_a_round_trip,
+    ),
+    ("values_survive_a_round_trip", values_survive_a_round_trip),
+    (
+        "random_values_survive_a_round_trip",
+        random_values_survive_a_round_trip,
+    ),
+    ("lossy_conversions", lossy_conversions),
+    ("handles_survive_a_round_trip", handles_survive_a_round_trip),
+    ("unsupported_variants", unsupported_variants),
+    ("unknown_exts_are_their_bytes", unknown_exts_are_their_bytes),
+    ("freed_objects_are_errors", freed_objects_are_errors),
+    (
+        "handles_are_called_from_signal_handlers",
+        handles_are_called_from_signal_handlers,
+    ),
+];
+
+#[godot_api]
+impl VimdowTests {
+    /// Runs every test, printing their results. Returns how many failed
+    #[func]
+    fn run(&self) -> i64 {
+        let mut failed = 0;
+        for (name, test) in TESTS {
+            match std::panic::catch_unwind(test) {
+                Ok(()) => godot_print!("ok {name}"),
+                Err(_) => {
+                    godot_error!("FAILED {name}");
+                    failed += 1;
+                }
+            }
+        }
+        godot_print!("{} passed, {failed} failed", TESTS.len() as i64 - failed);
+        failed
+    }
+}
+
+fn ext() -> ExtTypes {
+    ExtTypes::new(None, None)
+}
+
+fn variants_survive_a_round_trip() {
+    let variants = [
+        Variant::nil(),
+        true.to_variant(),
+        i64::MIN.to_variant(),
+        (-7).to_variant(),
+        1.5.to_variant(),
+        "日本 🦀".to_variant(),
+        PackedByteArray::from(vec![0, 255]).to_variant(),
+        varray![1, "a", &varray![true, &Variant::nil()]].to_variant(),
+        vdict! { "a" => 1, 2 => &varray![], "nested" => &vdict! { "b" => 2.5 } }.to_variant(),
+    ];
+    for v in variants {
+        let value = godot_to_rmpv(v.clone()).unwrap();
+        assert_eq!(rmpv_to_godot(value, &ext()).unwrap(), v);
+    }
+}
+
+fn values_survive_a_round_trip() {
+    let values = [
+        Value::Nil,
+        Value::Boolean(false),
+        Value::from(i64::MIN),
+        Value::from(i64::MAX),
+        Value::F64(-0.25),
+        Value::from("a\nb"),
+        Value::Binary(vec![1, 2, 3]),
+        Value::Array(vec![Value::from(1), Value::Array(vec![Value::Nil])]),
+        Value::Map(vec![(Value::from("k"), Value::Array(vec![]))]),
+    ];
+    for v in values {
+        let variant = rmpv_to_godot(v.clone(), &ext()).unwrap();
+        assert_eq!(godot_to_rmpv(variant).unwrap(), v);
+    }
+}
+
+// xorshift64*, like the properties of vimdow-core
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn bytes(&mut self) -> Vec<u8> {
+        (0..self.below(9)).map(|_| self.next() as u8).collect()
+    }
+
+    fn string(&mut self) -> String {
+        const PIECES: &[&str] = &["a", "Z", " ", "\n", "é", "日", "🦀", "\u{301}", ""];
+        (0..self.below(6))
+            .map(|_| PIECES[self.below(PIECES.len() as u64) as usize])
+            .collect()
+    }
+
+    // dictionary keys, which godot compares like rmpv does
+    fn key(&mut self) -> Value {
+        match self.below(2) {
+            0 => self.below(4).into(),
+            _ => self.string().into(),
+        }
+    }
+
+    /// Any value neovim can send, nested up to `depth` deep
+    fn value(&mut self, depth: u32) -> Value {
+        let kinds = if depth == 0 { 9 } else { 11 };
+        match self.below(kinds) {
+            0 => Value::Nil,
+            1 => Value::Boolean(self.below(2) == 0),
+            2 => match self.below(3) {
+                0 => self.next().into(),
+                1 => (self.next() as i64).into(),
+                _ => self.below(300).into(),
+            },
+            3 => Value::F32(self.next() as u32 as f32 / 7.0),
+            4 => Value::F64(self.next() as f64 / 3.0),
+            5 => self.string().into(),
+            6 => {
+                let bytes = self.bytes();
+                let mut str8 = vec![0xd9, bytes.len() as u8];
+                str8.extend(bytes);
+                rmpv::decode::read_value(&mut str8.as_slice()).unwrap()
+            }
+            7 => Value::Binary(self.bytes()),
+            8 => match self.below(2) {
+                0 => Handle {
+                    ext: self.below(3) as i8,
+                    id: self.next() as i64,
+                }
+                .into(),
+                _ => Value::Ext(3 + self.below(100) as i8, self.bytes()),
+            },
+            9 => Value::Array((0..self.below(4)).map(|_| self.value(depth - 1)).collect()),
+            _ => Value::Map(
+                (0..self.below(4))
+                    .map(|_| (self.key(), self.value(depth - 1)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// what a value becomes after going through godot, see the table in `msgpack`
+fn through_godot(v: &Value) -> Value {
+    match v {
+        Value::F32(f) => Value::F64(*f as f64),
+        Value::Integer(i) if i.as_i64().is_none() => i.to_string().into(),
+        Value::String(s) if s.as_str().is_none() => Value::Binary(s.as_bytes().to_vec()),
+        Value::Ext(ext, data) if !(0..3).contains(ext) => Value::Binary(data.clone()),
+        Value::Array(values) => Value::Array(values.iter().map(through_godot).collect()),
+        // a key that's there already gets its value replaced, where it was
+        Value::Map(map) => {
+            let mut entries: Vec<(Value, Value)> = vec![];
+            for (k, v) in map {
+                let (k, v) = (through_godot(k), through_godot(v));
+                match entries.iter_mut().find(|(key, _)| *key == k) {
+                    Some(entry) => entry.1 = v,
+                    None => entries.push((k, v)),
+                }
+            }
+            Value::Map(entries)
+        }
+        v => v.clone(),
+    }
+}
+
+fn random_values_survive_a_round_trip() {
+    for seed in [1, 0x5eed, 0xdead_beef] {
+        let mut rng = Rng(seed);
+        for _ in 0..300 {
+            let v = rng.value(3);
+            let variant = rmpv_to_godot(v.clone(), &ext()).unwrap();
+            assert_eq!(godot_to_rmpv(variant).unwrap(), through_godot(&v), "{v:?}");
+        }
+    }
+}
+
+fn lossy_conversions() {
+    // digits instead of a rounded int
+    let big = rmpv_to_godot(Value::from(u64::MAX), &ext()).unwrap();
+    assert_eq!(big, u64::MAX.to_string().to_variant());
+
+    let str8 = [0xd9, 2, 0xff, 0xfe];
+    let not_utf8 = rmpv::decode::read_value(&mut str8.as_slice()).unwrap();
+    let bytes = rmpv_to_godot(not_utf8, &ext()).unwrap();
+    assert_eq!(bytes, PackedByteArray::from(vec![0xff, 0xfe]).to_variant());
+
+    assert_eq!(Value::F64(1.0), godot_to_rmpv(1.0f32.to_variant()).unwrap());
+    assert_eq!(
+        godot_to_rmpv(Vector2i::new(3, -4).to_variant()).unwrap(),
+        Value::Array(vec![Value::from(3), Value::from(-4)])
+    );
+    assert_eq!(
+        godot_to_rmpv(Color::from_rgba(1.0, 0.5, 0.0, 1.0).to_variant()).unwrap(),
+        Value::Array([1.0, 0.5, 0.0, 1.0].map(Value::F64).to_vec())
+    );
+    assert_eq!(
+        godot_to_rmpv(StringName::from("name").to_variant()).unwrap(),
+        Value::from("name")
+    );
+}
+
+fn handles_survive_a_round_trip() {
+    for &(name, id) in EXT_TYPES {
+        let value = Value::from(Handle { ext: id, id: 1000 });
+        let variant = rmpv_to_godot(value.clone(), &ext()).unwrap();
+        let class = variant.to::<Gd<Object>>().get_class().to_string();
+        assert_eq!(class, format!("Neovim{name}"));
+        assert_eq!(godot_to_rmpv(variant).unwrap(), value);
+    }
+}
+
+fn unsupported_variants() {
+    let node = Node::new_alloc();
+    let result = godot_to_rmpv(node.to_variant());
+    node.free();
+    assert!(matches!(result, Err(ConversionError::Object(class)) if class == "Node"));
+
+    let result = godot_to_rmpv(Rid::Invalid.to_variant());
+    assert!(matches!(
+        result,
+        Err(ConversionError::Unsupported(VariantType::RID))
+    ));
+}
+
+fn unknown_exts_are_their_bytes() {
+    // a notification with an ext type of a newer neovim is still passed on
+    let params = vec![Value::from(1), Value::Ext(42, vec![0xc3, 7])];
+    let params = rpc_array_to_vararray(params, &ext()).unwrap();
+    assert_eq!(params.at(0), 1.to_variant());
+    assert_eq!(
+        params.at(1),
+        PackedByteArray::from(vec![0xc3, 7]).to_variant()
+    );
+}
+
+fn freed_objects_are_errors() {
+    let node = Node::new_alloc();
+    let freed = node.to_variant();
+    node.free();
+    assert!(matches!(
+        godot_to_rmpv(freed),
+        Err(ConversionError::FreedObject)
+    ));
+
+    let null = Option::<Gd<Node>>::None.to_variant();
+    assert_eq!(godot_to_rmpv(null).unwrap(), Value::Nil);
+}
+
+// a session file replaying one message from neovim
+fn session(message: Message) -> std::path::PathBuf {
+    let mut bytes = vec![];
+    rmpv::encode::write_value(&mut bytes, &message.into()).unwrap();
+    let entry = Value::Array(vec![0.into(), 0.into(), Value::Binary(bytes)]);
+    let mut file = vec![];
+    rmpv::encode::write_value(&mut file, &entry).unwrap();
+
+    let path = std::env::temp_dir().join("vimdow_itest.msgpack");
+    std::fs::write(&path, file).unwrap();
+    path
+}
+
+fn handles_are_called_from_signal_handlers() {
+    let path = session(Message::Notification {
+        method: "vimdow_itest".into(),
+        params: vec![Handle { ext: 0, id: 1 }.into()],
+    });
+    let mut client = NeovimClient::new_alloc();
+    assert!(
+        client
+            .bind_mut()
+            .replay(path.to_string_lossy().as_ref().into())
+    );
+
+    // the handler runs while `process` has the client bound
+    let answer = Rc::new(RefCell::new(None));
+    let answer_in = answer.clone();
+    client
+        .signals()
+        .neovim_event()
+        .connect(move |_method, params| {
+            // called through the engine, like a script would
+            let msgid = params
+                .at(0)
+                .call("get_lines", &[0.to_variant(), (-1).to_variant()]);
+            answer_in.replace(Some(msgid));
+        });
+    for _ in 0..100 {
+        client.bind_mut().process(0.0);
+        if answer.borrow().is_some() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    client.free();
+    let _ = std::fs::remove_file(path);
+
+    let msgid = answer.take().expect("the notification wasn't replayed");
+    assert!(matches!(msgid.try_to::<i32>(), Ok(msgid) if msgid >= 0));
+}
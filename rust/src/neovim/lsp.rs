@@ -0,0 +1,138 @@
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use godot::prelude::*;
+use rmpv::Value;
+
+use crate::neovim::api::NvimApi;
+use vimdow_core::process::NeovimProcess;
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(100);
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+// (re)starts the lsp client with the settings in g:vimdow_lsp, if the plugin is loaded
+const START_LSP_LUA: &str = "if Vimdow and Vimdow.start_lsp then Vimdow.start_lsp() end";
+
+enum State {
+    // configured without a port
+    Disabled,
+    Waiting {
+        next: Instant,
+        warned: bool,
+    },
+    // connecting takes up to PROBE_TIMEOUT, which the main thread can't wait for
+    Probing {
+        probe: JoinHandle<bool>,
+        warned: bool,
+    },
+    Connected,
+}
+
+/// Connects neovim to the editor's GDScript language server once it can be reached,
+/// and does so again whenever neovim loses it. How that goes is kept in `g:vimdow_lsp_status`
+#[derive(Default)]
+pub struct LanguageServer {
+    port: u16,
+    root_dir: String,
+    // nothing to connect to until `configure` is called
+    state: Option<State>,
+    // what `g:vimdow_lsp_status` was last set to
+    status: Option<&'static str>,
+}
+
+impl LanguageServer {
+    /// A `port` of 0 means there's no language server
+    pub fn configure(&mut self, port: u16, root_dir: String) {
+        self.port = port;
+        self.root_dir = root_dir;
+        self.state = Some(if port == 0 {
+            State::Disabled
+        } else {
+            State::Waiting {
+                next: Instant::now(),
+                warned: false,
+            }
+        });
+    }
+
+    /// Called when neovim's client exited, the server is probed again until it's back
+    pub fn lost(&mut self) {
+        if let Some(State::Connected) = self.state {
+            godot_warn!(
+                "Neovim lost the GDScript language server on port {}, reconnecting",
+                self.port
+            );
+            self.state = Some(State::Waiting {
+                next: Instant::now() + RETRY_INTERVAL,
+                warned: true,
+            });
+        }
+    }
+
+    fn probe(&self) -> JoinHandle<bool> {
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, self.port));
+        thread::spawn(move || TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_ok())
+    }
+
+    pub fn poll(&mut self, np: &mut NeovimProcess) {
+        self.state = match self.state.take() {
+            Some(State::Waiting { next, warned }) if Instant::now() >= next => {
+                Some(State::Probing {
+                    probe: self.probe(),
+                    warned,
+                })
+            }
+            Some(State::Probing { probe, warned }) if probe.is_finished() => {
+                if probe.join().unwrap_or(false) {
+                    self.connect(np, warned);
+                    Some(State::Connected)
+                } else {
+                    if !warned {
+                        godot_warn!(
+                            "The GDScript language server isn't reachable on port {}, \
+                            is it enabled in the editor settings? Retrying",
+                            self.port
+                        );
+                    }
+                    Some(State::Waiting {
+                        next: Instant::now() + RETRY_INTERVAL,
+                        warned: true,
+                    })
+                }
+            }
+            state => state,
+        };
+
+        let status = match self.state {
+            Some(State::Disabled) => "none",
+            Some(State::Connected) => "connected",
+            Some(State::Waiting { warned: true, .. } | State::Probing { warned: true, .. }) => {
+                "unreachable"
+            }
+            // not configured, or not probed yet
+            _ => return,
+        };
+        if self.status != Some(status) {
+            np.nvim_set_var("vimdow_lsp_status", status.into());
+            self.status = Some(status);
+        }
+    }
+
+    fn connect(&self, np: &mut NeovimProcess, warned: bool) {
+        np.nvim_set_var(
+            "vimdow_lsp",
+            Value::Map(vec![
+                ("port".into(), self.port.into()),
+                ("root_dir".into(), self.root_dir.as_str().into()),
+            ]),
+        );
+        np.nvim_exec_lua(START_LSP_LUA, vec![]);
+        if warned {
+            godot_print!(
+                "Connected to the GDScript language server on port {}",
+                self.port
+            );
+        }
+    }
+}
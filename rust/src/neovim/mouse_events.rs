@@ -1,10 +1,12 @@
-use crate::neovim::process::NeovimProcess;
 use godot::{
     classes::{InputEvent, InputEventMouse, InputEventMouseButton, InputEventMouseMotion},
     global::{MouseButton, MouseButtonMask},
     prelude::*,
 };
 
+use crate::neovim::api::NvimApi;
+use vimdow_core::process::NeovimProcess;
+
 fn make_mouse_modifiers(event: Gd<InputEventMouse>) -> String {
     let mut modifiers = String::new();
     if event.is_ctrl_pressed() {
@@ -22,6 +24,26 @@ fn make_mouse_modifiers(event: Gd<InputEventMouse>) -> String {
     modifiers
 }
 
+// buttons in the order a drag is attributed to when the press wasn't seen
+const DRAG_BUTTONS: [(MouseButtonMask, &str); 5] = [
+    (MouseButtonMask::LEFT, "left"),
+    (MouseButtonMask::RIGHT, "right"),
+    (MouseButtonMask::MIDDLE, "middle"),
+    (MouseButtonMask::MB_XBUTTON1, "x1"),
+    (MouseButtonMask::MB_XBUTTON2, "x2"),
+];
+
+/// Mouse state that has to persist between input flushes
+#[derive(Default)]
+pub struct MouseState {
+    /// mirrors neovim's 'mousemoveevent' option
+    pub move_events: bool,
+    // the button that started the current drag
+    pressed: Option<&'static str>,
+    // last cell a press or motion was reported for (grid, row, col)
+    last_cell: Option<(i32, i32, i32)>,
+}
+
 #[derive(Debug)]
 pub struct NvimInputMouse {
     button: String,
@@ -35,61 +57,76 @@ pub struct NvimInputMouse {
 impl NvimInputMouse {
     pub fn from_input_event(
         event: Gd<InputEvent>,
+        state: &mut MouseState,
         grid: i32,
         cell_size: Vector2,
     ) -> Option<Self> {
         if let Ok(mb) = event.clone().try_cast::<InputEventMouseButton>() {
             let pos = mb.get_position() / cell_size;
-            Self::button(mb, grid, pos)
+            Self::button(mb, state, grid, pos)
         } else if let Ok(mm) = event.clone().try_cast::<InputEventMouseMotion>() {
             let pos = mm.get_position() / cell_size;
-            Self::motion(mm, grid, pos)
+            Self::motion(mm, state, grid, pos)
         } else {
             None
         }
     }
 
     pub fn apply(&self, np: &mut NeovimProcess) -> i32 {
-        np.var_request(
-            "nvim_input_mouse",
-            varray![
-                self.button.as_str(),
-                self.action.as_str(),
-                self.modifier.as_str(),
-                self.grid,
-                self.row,
-                self.col
-            ],
+        np.nvim_input_mouse(
+            &self.button,
+            &self.action,
+            &self.modifier,
+            self.grid.into(),
+            self.row.into(),
+            self.col.into(),
         )
     }
 
-    fn motion(event: Gd<InputEventMouseMotion>, grid: i32, pos: Vector2) -> Option<Self> {
-        let modifier = make_mouse_modifiers(event.clone().upcast());
-
-        let button = match event.get_button_mask() {
-            MouseButtonMask::LEFT => "left",
-            MouseButtonMask::RIGHT => "right",
-            _ => "move",
+    fn motion(
+        event: Gd<InputEventMouseMotion>,
+        state: &mut MouseState,
+        grid: i32,
+        pos: Vector2,
+    ) -> Option<Self> {
+        let (row, col) = (pos.y as i32, pos.x as i32);
+        // neovim only cares about which cell the mouse is in
+        if state.last_cell == Some((grid, row, col)) {
+            return None;
         }
-        .to_string();
-        let action = if event.get_button_mask().ord() != 0 {
-            "drag"
+
+        let mask = event.get_button_mask().ord();
+        let (button, action) = if mask != 0 {
+            let button = state.pressed.or_else(|| {
+                DRAG_BUTTONS
+                    .iter()
+                    .find(|(m, _)| mask & m.ord() != 0)
+                    .map(|(_, b)| *b)
+            })?;
+            (button, "drag")
+        } else if state.move_events {
+            ("move", "") // action is ignored for moves
         } else {
-            "release" // shouldn't matter if it's moved
-        }
-        .to_string();
+            return None;
+        };
+        state.last_cell = Some((grid, row, col));
 
         Some(Self {
-            button,
-            action,
-            modifier,
+            button: button.to_string(),
+            action: action.to_string(),
+            modifier: make_mouse_modifiers(event.upcast()),
             grid,
-            row: pos.y as i32,
-            col: pos.x as i32,
+            row,
+            col,
         })
     }
 
-    fn button(event: Gd<InputEventMouseButton>, grid: i32, pos: Vector2) -> Option<Self> {
+    fn button(
+        event: Gd<InputEventMouseButton>,
+        state: &mut MouseState,
+        grid: i32,
+        pos: Vector2,
+    ) -> Option<Self> {
         let modifier = make_mouse_modifiers(event.clone().upcast());
         let mut action = if event.is_pressed() {
             "press"
@@ -126,16 +163,25 @@ impl NvimInputMouse {
                 "wheel"
             }
             _ => return None,
+        };
+
+        let (row, col) = (pos.y as i32, pos.x as i32);
+        if button != "wheel" {
+            if event.is_pressed() {
+                state.pressed.get_or_insert(button);
+            } else if state.pressed == Some(button) {
+                state.pressed = None;
+            }
+            state.last_cell = Some((grid, row, col));
         }
-        .to_string();
 
         Some(Self {
-            button,
+            button: button.to_string(),
             action,
             modifier,
             grid,
-            row: pos.y as i32,
-            col: pos.x as i32,
+            row,
+            col,
         })
     }
 }
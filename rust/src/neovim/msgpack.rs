@@ -1,84 +1,292 @@
-use crate::neovim::ext_types::rmpv_ext_to_godot;
+//! Conversion between msgpack values and Godot Variants.
+//!
+//! From neovim to Godot:
+//!
+//! | msgpack | Variant |
+//! | --- | --- |
+//! | nil | `null` |
+//! | boolean | `bool` |
+//! | integer | `int`, or a `String` of its digits for a u64 past `i64::MAX` |
+//! | float 32/64 | `float` |
+//! | string | `String`, or a `PackedByteArray` of its bytes when it isn't UTF-8 |
+//! | binary | `PackedByteArray` |
+//! | array | `Array` |
+//! | map | `Dictionary` |
+//! | ext Buffer/Window/Tabpage | `NeovimBuffer`/`NeovimWindow`/`NeovimTabpage` |
+//! | other ext types | `PackedByteArray` of their data, with a warning |
+//!
+//! From Godot to neovim:
+//!
+//! | Variant | msgpack |
+//! | --- | --- |
+//! | `null`, a null `Object` | nil |
+//! | `bool` | boolean |
+//! | `int` | integer |
+//! | `float` | float 64 |
+//! | `String`, `StringName`, `NodePath` | string |
+//! | `PackedByteArray` | binary |
+//! | `Array`, other packed arrays | array of their elements |
+//! | `Dictionary` | map |
+//! | `Vector2/3/4`, `Vector2i/3i/4i`, `Quaternion` | array of the components |
+//! | `Color` | array of r, g, b, a |
+//! | `Rect2`, `Rect2i`, `AABB` | array of the position and size arrays |
+//! | `Plane` | array of the normal's components and d |
+//! | `Transform2D` | array of the x, y and origin arrays |
+//! | `Basis` | array of the row arrays |
+//! | `Transform3D` | array of the basis rows and origin arrays |
+//! | `Projection` | array of the column arrays |
+//! | `NeovimBuffer`/`NeovimWindow`/`NeovimTabpage` | ext Buffer/Window/Tabpage |
+//! | a `Resource` saved to a file | string of its path |
+//!
+//! Handles keep the ext type id of the neovim that sent them, see `ExtTypes`.
+//! Other objects, `Callable`, `Signal` and `RID` have no meaning outside of Godot and
+//! can't be sent, neither can freed objects.
+
+use crate::neovim::ext_types::{Buffer, ExtTypes, Tabpage, Window};
+use godot::classes::{Object, Resource};
 use godot::prelude::*;
 use rmpv::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConversionError {
+    #[error("Can't send a {0:?} to neovim")]
+    Unsupported(VariantType),
+    #[error("Can't send an instance of {0} to neovim, only resources saved to a file")]
+    Object(String),
+    #[error("Can't send a freed object to neovim")]
+    FreedObject,
+    #[error("Ext type {0} doesn't hold a handle")]
+    InvalidExt(i8),
+}
 
-pub fn rmpv_to_godot(v: Value) -> Variant {
-    match v {
+pub fn rmpv_to_godot(v: Value, ext: &ExtTypes) -> Result<Variant, ConversionError> {
+    Ok(match v {
         Value::Nil => Variant::nil(),
-        Value::Array(values) => {
-            let mut out = VarArray::new();
-            for value in values {
-                out.push(&rmpv_to_godot(value));
+        Value::Array(values) => rpc_array_to_vararray(values, ext)?.to_variant(),
+        // only u64s past i64::MAX don't fit, their digits are kept instead of rounding them
+        Value::Integer(i) => match i.as_i64() {
+            Some(i) => i.to_variant(),
+            None => {
+                godot_warn!("Neovim sent {i}, which doesn't fit an int");
+                i.to_string().to_variant()
             }
-            out.to_variant()
-        }
-        Value::Integer(i) => i.as_i64().expect("Not an i64").to_variant(),
+        },
         Value::F32(f) => f.to_variant(),
         Value::F64(f) => f.to_variant(),
         Value::Map(map) => {
             let mut dict = vdict! {};
             for (k, v) in map {
-                let _ = dict.insert(&rmpv_to_godot(k), &rmpv_to_godot(v));
+                let _ = dict.insert(&rmpv_to_godot(k, ext)?, &rmpv_to_godot(v, ext)?);
             }
             dict.to_variant()
         }
-        Value::Ext(t, data) => {
-            let e = rmpv_ext_to_godot(t, data);
-            if e.is_nil() {
-                godot_error!("Unhandled ext type: {t}");
+        Value::Ext(t, data) => ext.decode(t, &data)?,
+        Value::String(s) => match s.as_str() {
+            Some(s) => s.to_variant(),
+            None => {
+                godot_warn!("Neovim sent a string that isn't UTF-8");
+                PackedByteArray::from(s.into_bytes()).to_variant()
             }
-            e
-        }
-        Value::String(s) => s.into_str().unwrap().to_variant(),
+        },
         Value::Boolean(b) => b.to_variant(),
-        Value::Binary(_bin) => todo!(), // neovim doesn't use this yet
+        Value::Binary(bin) => PackedByteArray::from(bin).to_variant(),
+    })
+}
+
+fn components<T: Into<f64>>(c: impl IntoIterator<Item = T>) -> Value {
+    Value::Array(c.into_iter().map(|c| Value::F64(c.into())).collect())
+}
+
+fn int_components(c: impl IntoIterator<Item = i32>) -> Value {
+    Value::Array(c.into_iter().map(Value::from).collect())
+}
+
+fn vector2(v: Vector2) -> Value {
+    components([v.x, v.y])
+}
+
+fn vector3(v: Vector3) -> Value {
+    components([v.x, v.y, v.z])
+}
+
+fn vector4(v: Vector4) -> Value {
+    components([v.x, v.y, v.z, v.w])
+}
+
+fn color(c: Color) -> Value {
+    components([c.r, c.g, c.b, c.a])
+}
+
+fn basis(b: Basis) -> Vec<Value> {
+    b.rows.into_iter().map(vector3).collect()
+}
+
+fn object(v: &Variant) -> Result<Value, ConversionError> {
+    // null objects are nil variants, so this only fails on freed ones
+    let obj = v
+        .try_to::<Gd<Object>>()
+        .map_err(|_| ConversionError::FreedObject)?;
+    let obj = match obj.try_cast::<Buffer>() {
+        Ok(buffer) => return Ok(Value::from(&*buffer.bind())),
+        Err(obj) => obj,
+    };
+    let obj = match obj.try_cast::<Window>() {
+        Ok(window) => return Ok(Value::from(&*window.bind())),
+        Err(obj) => obj,
+    };
+    let obj = match obj.try_cast::<Tabpage>() {
+        Ok(tabpage) => return Ok(Value::from(&*tabpage.bind())),
+        Err(obj) => obj,
+    };
+    match obj.try_cast::<Resource>() {
+        Ok(res) if !res.get_path().is_empty() => {
+            Ok(Value::String(res.get_path().to_string().into()))
+        }
+        Ok(res) => Err(ConversionError::Object(res.get_class().to_string())),
+        Err(obj) => Err(ConversionError::Object(obj.get_class().to_string())),
     }
 }
 
-pub fn godot_to_rmpv(v: Variant) -> Value {
+pub fn godot_to_rmpv(v: Variant) -> Result<Value, ConversionError> {
     let t = v.get_type();
-    match t {
+    Ok(match t {
         VariantType::NIL => Value::Nil,
-        VariantType::ARRAY => {
-            let mut out = Vec::new();
-            let v = v.to::<Vec<Variant>>();
-            for var in v {
-                out.push(godot_to_rmpv(var));
-            }
-
-            Value::Array(out)
-        }
         VariantType::BOOL => Value::Boolean(v.to()),
-        VariantType::INT => {
-            if let Ok(int64) = v.try_to::<i64>() {
-                Value::Integer(int64.into())
-            } else {
-                panic!("Can't turn {v} into msgpack int");
-            }
+        VariantType::INT => Value::Integer(v.to::<i64>().into()),
+        VariantType::FLOAT => Value::F64(v.to()),
+        VariantType::STRING | VariantType::STRING_NAME | VariantType::NODE_PATH => {
+            Value::String(v.to_string().into())
         }
-        VariantType::FLOAT => {
-            if let Ok(float32) = v.try_to::<f32>() {
-                Value::F32(float32)
-            } else if let Ok(float64) = v.try_to::<f64>() {
-                Value::F64(float64)
-            } else {
-                panic!("Can't turn {v} into msgpack float");
-            }
+        VariantType::ARRAY => Value::Array(vararray_to_rpc(v.to())?),
+        VariantType::DICTIONARY => Value::Map(dict_to_rpc(v.to())?),
+        VariantType::OBJECT => object(&v)?,
+
+        VariantType::PACKED_BYTE_ARRAY => Value::Binary(v.to::<PackedByteArray>().to_vec()),
+        VariantType::PACKED_INT32_ARRAY => Value::Array(
+            v.to::<PackedInt32Array>()
+                .as_slice()
+                .iter()
+                .map(|&i| Value::from(i))
+                .collect(),
+        ),
+        VariantType::PACKED_INT64_ARRAY => Value::Array(
+            v.to::<PackedInt64Array>()
+                .as_slice()
+                .iter()
+                .map(|&i| Value::from(i))
+                .collect(),
+        ),
+        VariantType::PACKED_FLOAT32_ARRAY => {
+            components(v.to::<PackedFloat32Array>().as_slice().iter().copied())
         }
-        VariantType::STRING => Value::String(v.to_string().into()),
-        VariantType::STRING_NAME => Value::String(v.to_string().into()),
-        VariantType::DICTIONARY => Value::Map(
-            v.to::<VarDictionary>()
-                .iter_shared()
-                .map(|(v1, v2)| (godot_to_rmpv(v1), godot_to_rmpv(v2)))
+        VariantType::PACKED_FLOAT64_ARRAY => {
+            components(v.to::<PackedFloat64Array>().as_slice().iter().copied())
+        }
+        VariantType::PACKED_STRING_ARRAY => Value::Array(
+            v.to::<PackedStringArray>()
+                .as_slice()
+                .iter()
+                .map(|s| Value::String(s.to_string().into()))
                 .collect(),
         ),
-        _ => panic!("Can't represent {t:?} as message pack"),
-    }
+        VariantType::PACKED_VECTOR2_ARRAY => Value::Array(
+            v.to::<PackedVector2Array>()
+                .as_slice()
+                .iter()
+                .map(|&v| vector2(v))
+                .collect(),
+        ),
+        VariantType::PACKED_VECTOR3_ARRAY => Value::Array(
+            v.to::<PackedVector3Array>()
+                .as_slice()
+                .iter()
+                .map(|&v| vector3(v))
+                .collect(),
+        ),
+        VariantType::PACKED_VECTOR4_ARRAY => Value::Array(
+            v.to::<PackedVector4Array>()
+                .as_slice()
+                .iter()
+                .map(|&v| vector4(v))
+                .collect(),
+        ),
+        VariantType::PACKED_COLOR_ARRAY => Value::Array(
+            v.to::<PackedColorArray>()
+                .as_slice()
+                .iter()
+                .map(|&c| color(c))
+                .collect(),
+        ),
+
+        VariantType::VECTOR2 => vector2(v.to()),
+        VariantType::VECTOR3 => vector3(v.to()),
+        VariantType::VECTOR4 => vector4(v.to()),
+        VariantType::VECTOR2I => {
+            let v = v.to::<Vector2i>();
+            int_components([v.x, v.y])
+        }
+        VariantType::VECTOR3I => {
+            let v = v.to::<Vector3i>();
+            int_components([v.x, v.y, v.z])
+        }
+        VariantType::VECTOR4I => {
+            let v = v.to::<Vector4i>();
+            int_components([v.x, v.y, v.z, v.w])
+        }
+        VariantType::QUATERNION => {
+            let q = v.to::<Quaternion>();
+            components([q.x, q.y, q.z, q.w])
+        }
+        VariantType::COLOR => color(v.to()),
+        VariantType::RECT2 => {
+            let r = v.to::<Rect2>();
+            Value::Array(vec![vector2(r.position), vector2(r.size)])
+        }
+        VariantType::RECT2I => {
+            let r = v.to::<Rect2i>();
+            Value::Array(vec![
+                int_components([r.position.x, r.position.y]),
+                int_components([r.size.x, r.size.y]),
+            ])
+        }
+        VariantType::AABB => {
+            let b = v.to::<Aabb>();
+            Value::Array(vec![vector3(b.position), vector3(b.size)])
+        }
+        VariantType::PLANE => {
+            let p = v.to::<Plane>();
+            components([p.normal.x, p.normal.y, p.normal.z, p.d])
+        }
+        VariantType::TRANSFORM2D => {
+            let t = v.to::<Transform2D>();
+            Value::Array(vec![vector2(t.a), vector2(t.b), vector2(t.origin)])
+        }
+        VariantType::BASIS => Value::Array(basis(v.to())),
+        VariantType::TRANSFORM3D => {
+            let t = v.to::<Transform3D>();
+            let mut rows = basis(t.basis);
+            rows.push(vector3(t.origin));
+            Value::Array(rows)
+        }
+        VariantType::PROJECTION => {
+            Value::Array(v.to::<Projection>().cols.into_iter().map(vector4).collect())
+        }
+
+        _ => return Err(ConversionError::Unsupported(t)),
+    })
+}
+
+pub fn vararray_to_rpc(arr: VarArray) -> Result<Vec<Value>, ConversionError> {
+    arr.iter_shared().map(godot_to_rmpv).collect()
+}
+
+pub fn dict_to_rpc(dict: VarDictionary) -> Result<Vec<(Value, Value)>, ConversionError> {
+    dict.iter_shared()
+        .map(|(k, v)| Ok((godot_to_rmpv(k)?, godot_to_rmpv(v)?)))
+        .collect()
 }
 
-pub fn rpc_array_to_vararray(arr: Vec<Value>) -> VarArray {
-    rmpv_to_godot(Value::Array(arr))
-        .try_to()
-        .expect("Couldn't convert to var array")
+pub fn rpc_array_to_vararray(arr: Vec<Value>, ext: &ExtTypes) -> Result<VarArray, ConversionError> {
+    arr.into_iter().map(|v| rmpv_to_godot(v, ext)).collect()
 }
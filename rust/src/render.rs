@@ -0,0 +1,443 @@
+//! Lays out the rows of the grid as draw commands. The commands are plain data,
+//! which lets what `VimdowWindow` draws be checked without a renderer
+
+use std::f32::consts::TAU;
+use std::fmt;
+
+use godot::builtin::{Color, Vector2};
+use rmpv::Value;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The theme fonts of `VimdowEditor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontStyle {
+    Normal,
+    Bold,
+    Italic,
+}
+
+impl FontStyle {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Bold => "bold",
+            Self::Italic => "italic",
+        }
+    }
+
+    /// The font of the attributes of an `hl_attr_define` event
+    pub fn from_attrs(attrs: &[(String, Value)]) -> Self {
+        if attr(attrs, "bold").is_some() {
+            Self::Bold
+        } else if attr(attrs, "italic").is_some() {
+            Self::Italic
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+/// The size of the font a highlight is drawn with
+#[derive(Debug, Clone, Copy)]
+pub struct FontMetrics {
+    pub font_size: i32,
+    pub char_size: Vector2,
+    pub ascent: f32,
+    pub descent: f32,
+}
+
+// an rgb color of neovim, like 0xff0000 for red
+fn rgb(rgb: i64) -> Color {
+    Color::from_rgb(
+        ((rgb >> 16) & 0xff) as f32 / 255.0,
+        ((rgb >> 8) & 0xff) as f32 / 255.0,
+        (rgb & 0xff) as f32 / 255.0,
+    )
+}
+
+fn attr<'a>(attrs: &'a [(String, Value)], key: &str) -> Option<&'a Value> {
+    attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+/// How a highlight is drawn, with the metrics of its font
+#[derive(Debug, Clone)]
+pub struct HlAttr {
+    pub foreground: Color,
+    pub background: Color,
+    pub special: Color,
+    pub font: FontStyle,
+    pub undercurl: bool,
+    pub underline: bool,
+    pub underdouble: bool,
+    pub underdotted: bool,
+    pub underdashed: bool,
+    pub strikethrough: bool,
+    pub url: bool,
+    pub font_size: i32,
+    pub char_size: Vector2,
+    pub ascent: f32,
+    pub descent: f32,
+}
+
+impl HlAttr {
+    /// How the attributes of an `hl_attr_define` event are drawn, check
+    /// ":h ui-event-hl_attr_define". Flags are only sent when set, so they're
+    /// checked by presence, and missing colors are the default ones
+    pub fn new(
+        attrs: &[(String, Value)],
+        (default_fg, default_bg, default_sp): (i64, i64, i64),
+        metrics: FontMetrics,
+    ) -> Self {
+        let has = |key| attr(attrs, key).is_some();
+        let color = |key, default| rgb(attr(attrs, key).and_then(Value::as_i64).unwrap_or(default));
+
+        let (mut foreground, mut background) = (
+            color("foreground", default_fg),
+            color("background", default_bg),
+        );
+        if has("reverse") {
+            (foreground, background) = (background, foreground);
+        }
+        let mut special = color("special", default_sp);
+
+        if let Some(blend_pct) = attr(attrs, "blend")
+            .and_then(Value::as_i64)
+            .map(|i| (100.0 - i as f32) / 100.0)
+        {
+            foreground.a = blend_pct;
+            background.a = blend_pct;
+            special.a = blend_pct;
+        }
+
+        Self {
+            foreground,
+            background,
+            special,
+            font: FontStyle::from_attrs(attrs),
+            undercurl: has("undercurl"),
+            underline: has("underline"),
+            underdouble: has("underdouble"),
+            underdotted: has("underdotted"),
+            underdashed: has("underdashed"),
+            strikethrough: has("strikethrough"),
+            url: has("url"),
+            font_size: metrics.font_size,
+            char_size: metrics.char_size,
+            ascent: metrics.ascent,
+            descent: metrics.descent,
+        }
+    }
+}
+
+pub struct Region {
+    pub start_col: usize,
+    pub end_col: usize,
+    pub attr: HlAttr,
+}
+
+impl Region {
+    fn len(&self) -> usize {
+        self.end_col - self.start_col
+    }
+}
+
+/// The `cursor_shape` of a mode, see ":h ui-event-mode_info_set"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    Vertical,
+    Horizontal,
+}
+
+impl CursorShape {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "block" => Some(Self::Block),
+            "vertical" => Some(Self::Vertical),
+            "horizontal" => Some(Self::Horizontal),
+            _ => None,
+        }
+    }
+}
+
+pub struct Cursor {
+    pub col: usize,
+    pub shape: CursorShape,
+    pub attr: HlAttr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    Rect {
+        position: Vector2,
+        size: Vector2,
+        color: Color,
+    },
+    Text {
+        font: FontStyle,
+        font_size: i32,
+        position: Vector2,
+        text: String,
+        color: Color,
+    },
+    Polyline {
+        points: Vec<Vector2>,
+        color: Color,
+        width: f32,
+    },
+    Line {
+        from: Vector2,
+        to: Vector2,
+        color: Color,
+    },
+}
+
+struct V(Vector2);
+
+impl fmt::Display for V {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({:.2}, {:.2})", self.0.x, self.0.y)
+    }
+}
+
+struct C(Color);
+
+impl fmt::Display for C {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = self.0;
+        let byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        write!(
+            f,
+            "#{:02x}{:02x}{:02x}{:02x}",
+            byte(c.r),
+            byte(c.g),
+            byte(c.b),
+            byte(c.a)
+        )
+    }
+}
+
+// one line per command, stable enough to check in
+impl fmt::Display for DrawCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rect {
+                position,
+                size,
+                color,
+            } => write!(f, "rect {} {} {}", V(*position), V(*size), C(*color)),
+            Self::Text {
+                font,
+                font_size,
+                position,
+                text,
+                color,
+            } => write!(
+                f,
+                "text {} {font_size} {} {text:?} {}",
+                font.name(),
+                V(*position),
+                C(*color)
+            ),
+            Self::Polyline {
+                points,
+                color,
+                width,
+            } => {
+                write!(f, "polyline {width:.2} {}", C(*color))?;
+                for p in points {
+                    write!(f, " {}", V(*p))?;
+                }
+                Ok(())
+            }
+            Self::Line { from, to, color } => {
+                write!(f, "line {} {} {}", V(*from), V(*to), C(*color))
+            }
+        }
+    }
+}
+
+fn column_slice(row: &str, start: usize, end: usize) -> String {
+    assert!(start <= end);
+    row.graphemes(true).skip(start).take(end - start).collect()
+}
+
+fn get_column(row: &str, col: usize) -> String {
+    row.graphemes(true).nth(col).unwrap_or(" ").to_string()
+}
+
+/// The commands drawing a row of the grid, with the cursor on top if it's in the row
+pub fn draw_row(
+    row: usize,
+    text: &str,
+    regions: &[Region],
+    cursor: Option<&Cursor>,
+    ignore_hl: bool,
+) -> Vec<DrawCommand> {
+    let mut out = vec![];
+    if !ignore_hl {
+        // drawing background colors
+        for r in regions {
+            out.push(DrawCommand::Rect {
+                position: Vector2::new(
+                    r.attr.char_size.x * r.start_col as f32,
+                    r.attr.char_size.y * row as f32,
+                ),
+                size: Vector2::new(r.attr.char_size.x * r.len() as f32, r.attr.char_size.y),
+                color: r.attr.background,
+            });
+        }
+    }
+
+    for r in regions {
+        let text_position = Vector2::new(
+            r.start_col as f32 * r.attr.char_size.x,
+            row as f32 * r.attr.char_size.y + r.attr.ascent,
+        );
+
+        out.push(DrawCommand::Text {
+            font: r.attr.font,
+            font_size: r.attr.font_size,
+            position: text_position,
+            text: column_slice(text, r.start_col, r.end_col),
+            color: if ignore_hl {
+                Color::WHITE
+            } else {
+                r.attr.foreground
+            },
+        });
+
+        draw_decorations(&mut out, text_position, r);
+    }
+
+    if let Some(cursor) = cursor {
+        draw_cursor(&mut out, row, text, cursor);
+    }
+    out
+}
+
+fn draw_decorations(out: &mut Vec<DrawCommand>, text_position: Vector2, r: &Region) {
+    let attr = &r.attr;
+    let l = r.len();
+    let desc = attr.descent;
+
+    if attr.undercurl {
+        let amplitude = desc / 2.0;
+        const STEPS_PER_CYCLE: usize = 8;
+        let total_span = l * STEPS_PER_CYCLE;
+        let points = (0..total_span)
+            .map(|i| {
+                let t = i as f32 / total_span as f32;
+                Vector2::new(
+                    text_position.x + t * l as f32 * attr.char_size.x,
+                    text_position.y + amplitude - (l as f32 * t * TAU).cos() * amplitude,
+                )
+            })
+            .collect();
+        out.push(DrawCommand::Polyline {
+            points,
+            color: attr.special,
+            width: attr.font_size as f32 * 0.1,
+        });
+    }
+
+    if attr.strikethrough {
+        // through the middle of the lowercase letters, about half the x-height
+        draw_underline(out, text_position, l, -attr.ascent * 0.3, 2.0, attr);
+    }
+
+    if attr.underline || attr.url {
+        draw_underline(out, text_position, l, desc, 2.0, attr);
+    }
+
+    if attr.underdouble {
+        // two thinner lines, both between the baseline and the bottom of the cell
+        draw_underline(out, text_position, l, desc, 1.0, attr);
+        draw_underline(out, text_position, l, desc / 2.0, 1.0, attr);
+    }
+
+    if attr.underdashed {
+        draw_segmented_underline(out, text_position, 0.33, 2, l, attr);
+    }
+
+    if attr.underdotted {
+        draw_segmented_underline(out, text_position, 0.15, 3, l, attr);
+    }
+}
+
+fn draw_underline(
+    out: &mut Vec<DrawCommand>,
+    text_position: Vector2,
+    region_len: usize,
+    y: f32,
+    width: f32,
+    attr: &HlAttr,
+) {
+    out.push(DrawCommand::Polyline {
+        points: vec![
+            Vector2::new(text_position.x, text_position.y + y),
+            Vector2::new(
+                text_position.x + region_len as f32 * attr.char_size.x,
+                text_position.y + y,
+            ),
+        ],
+        color: attr.special,
+        width,
+    });
+}
+
+fn draw_segmented_underline(
+    out: &mut Vec<DrawCommand>,
+    text_position: Vector2,
+    segment_len: f32,
+    segments_per_char: i32,
+    region_len: usize,
+    attr: &HlAttr,
+) {
+    let y = text_position.y + attr.descent;
+    for i in 0..region_len {
+        let left = text_position.x + i as f32 * attr.char_size.x;
+        let right = left + attr.char_size.x * segment_len;
+
+        for j in 0..segments_per_char {
+            let offset = j as f32 / segments_per_char as f32 * attr.char_size.x;
+            out.push(DrawCommand::Line {
+                from: Vector2::new(left + offset, y),
+                to: Vector2::new(right + offset, y),
+                color: attr.special,
+            });
+        }
+    }
+}
+
+fn draw_cursor(out: &mut Vec<DrawCommand>, row: usize, text: &str, cursor: &Cursor) {
+    let attr = &cursor.attr;
+    let cs = attr.char_size;
+    let position = Vector2::new(cursor.col as f32, row as f32) * cs;
+    match cursor.shape {
+        CursorShape::Block => {
+            out.push(DrawCommand::Rect {
+                position,
+                size: cs,
+                color: attr.foreground,
+            });
+            out.push(DrawCommand::Text {
+                font: attr.font,
+                font_size: attr.font_size,
+                position: Vector2::new(position.x, position.y + attr.ascent),
+                text: get_column(text, cursor.col),
+                color: attr.background,
+            });
+        }
+        CursorShape::Vertical => out.push(DrawCommand::Line {
+            from: position,
+            to: Vector2::new(position.x, position.y + cs.y),
+            color: attr.foreground,
+        }),
+        CursorShape::Horizontal => out.push(DrawCommand::Line {
+            from: Vector2::new(position.x, position.y + cs.y),
+            to: Vector2::new(position.x + cs.x, position.y + cs.y),
+            color: attr.foreground,
+        }),
+    }
+}
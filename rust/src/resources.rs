@@ -0,0 +1,127 @@
+use godot::classes::resource_loader::CacheMode;
+use godot::classes::{FileAccess, ProjectSettings, Resource, ResourceLoader, Script};
+use godot::global::Error;
+use godot::prelude::*;
+
+const EXTENSIONS_SETTING: &str = "vimdow/handled_extensions";
+
+/// Decides which resources vimdow edits in place of godot's built-in editors,
+/// and maps them to the files neovim should open
+#[derive(GodotClass)]
+#[class(tool, init, base=RefCounted)]
+pub struct ResourceResolver {
+    base: Base<RefCounted>,
+}
+
+impl ResourceResolver {
+    // built-in resources are saved inside of another file
+    fn is_file(path: &GString) -> bool {
+        !path.is_empty() && !path.contains("::")
+    }
+
+    fn handles_path(path: &GString) -> bool {
+        if !Self::is_file(path) {
+            return false;
+        }
+
+        let extension = path.get_extension().to_lower();
+        ProjectSettings::singleton()
+            .get_setting(EXTENSIONS_SETTING)
+            .try_to::<PackedStringArray>()
+            .map(|exts| exts.as_slice().iter().any(|e| e.to_lower() == extension))
+            .unwrap_or(false)
+    }
+
+    // the loaded script that unsaved `text` would be parsed for, if it differs from it.
+    // Tool scripts are skipped since they run in the editor
+    fn live_text_script(path: &GString, text: &GString) -> Option<Gd<Script>> {
+        let local = ProjectSettings::singleton().localize_path(path);
+        ResourceLoader::singleton()
+            .get_cached_ref(&local)
+            .and_then(|r| r.try_cast::<Script>().ok())
+            .filter(|s| !s.is_tool() && s.get_source_code() != *text)
+    }
+}
+
+#[godot_api]
+impl ResourceResolver {
+    /// Absolute path of the file vimdow should open for `object`,
+    /// or an empty string if it's not a resource vimdow owns
+    #[func]
+    fn resolve(&self, object: Option<Gd<Object>>) -> GString {
+        let Some(res) = object.and_then(|o| o.try_cast::<Resource>().ok()) else {
+            return GString::new();
+        };
+
+        // scripts of every language are edited in vimdow, whatever their extension
+        let path = res.get_path();
+        let is_script = res.is_class("Script");
+        if Self::handles_path(&path) || (is_script && Self::is_file(&path)) {
+            ProjectSettings::singleton().globalize_path(&path)
+        } else {
+            GString::new()
+        }
+    }
+
+    #[func]
+    fn handles(&self, object: Option<Gd<Object>>) -> bool {
+        !self.resolve(object).is_empty()
+    }
+
+    /// Whether vimdow edits the file at `path`, which can be absolute or project relative
+    #[func]
+    fn handles_file(&self, path: GString) -> bool {
+        Self::handles_path(&ProjectSettings::singleton().localize_path(&path))
+    }
+
+    /// Reloads a file neovim wrote to disk, if godot has it loaded as a resource.
+    /// Scripts are re-parsed in place so that their instances keep their state
+    #[func]
+    fn reload(&self, path: GString) {
+        let local = ProjectSettings::singleton().localize_path(&path);
+        let mut loader = ResourceLoader::singleton();
+        if !Self::is_file(&local) || !loader.has_cached(&local) {
+            return;
+        }
+
+        let cached = loader.get_cached_ref(&local);
+        if let Some(mut script) = cached.and_then(|r| r.try_cast::<Script>().ok()) {
+            script.set_source_code(&FileAccess::get_file_as_string(&local));
+            let err = script.reload_ex().keep_state(true).done();
+            if err != Error::OK {
+                godot_warn!("Couldn't re-parse '{local}': {err:?}");
+            }
+        } else if Self::handles_path(&local)
+            && loader
+                .load_ex(&local)
+                .cache_mode(CacheMode::REPLACE)
+                .done()
+                .is_none()
+        {
+            godot_error!("Couldn't reload '{local}'");
+        }
+    }
+
+    /// Whether `apply_live_text` would parse `text` for a script
+    #[func]
+    fn needs_live_text(&self, path: GString, text: GString) -> bool {
+        Self::live_text_script(&path, &text).is_some()
+    }
+
+    /// Parses unsaved text from neovim, so that godot reports its errors without waiting
+    /// for a save
+    #[func]
+    fn apply_live_text(&self, path: GString, text: GString) {
+        let Some(script) = Self::live_text_script(&path, &text) else {
+            return;
+        };
+
+        // a copy outside of the resource cache keeps the unsaved text out of what godot
+        // saves, and out of what it has loaded when neovim drops the text
+        let mut copy = script.duplicate_resource();
+        copy.set_path_cache(&script.get_path());
+        copy.set_source_code(&text);
+        // parse errors get reported by godot itself
+        let _ = copy.reload();
+    }
+}
@@ -0,0 +1,230 @@
+//! Checks the draw commands of `VimdowWindow` against the golden files in tests/golden.
+//! Run with `UPDATE_GOLDENS=1` to write them after a deliberate rendering change
+
+use std::path::PathBuf;
+
+use godot::builtin::{Color, Vector2};
+use rmpv::Value;
+use vimdow::render::{
+    Cursor, CursorShape, DrawCommand, FontMetrics, FontStyle, HlAttr, Region, draw_row,
+};
+
+const TEXT: &str = "let x = 1\n";
+
+fn default_attr() -> HlAttr {
+    HlAttr {
+        foreground: Color::from_rgb(0.9, 0.9, 0.9),
+        background: Color::from_rgb(0.1, 0.1, 0.1),
+        special: Color::from_rgb(1.0, 0.0, 0.0),
+        font: FontStyle::Normal,
+        undercurl: false,
+        underline: false,
+        underdouble: false,
+        underdotted: false,
+        underdashed: false,
+        strikethrough: false,
+        url: false,
+        font_size: 16,
+        char_size: Vector2::new(10.0, 20.0),
+        ascent: 15.0,
+        descent: 4.0,
+    }
+}
+
+// "let" drawn with `attr`, the rest of the row with the default highlight
+fn regions(attr: HlAttr) -> Vec<Region> {
+    vec![
+        Region {
+            start_col: 0,
+            end_col: 3,
+            attr,
+        },
+        Region {
+            start_col: 3,
+            end_col: 9,
+            attr: default_attr(),
+        },
+    ]
+}
+
+fn check_golden(name: &str, commands: &[DrawCommand]) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.txt"));
+    let actual: String = commands.iter().map(|c| format!("{c}\n")).collect();
+
+    if std::env::var_os("UPDATE_GOLDENS").is_some() {
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Couldn't read {}: {e}", path.display()));
+    assert!(
+        expected == actual,
+        "{name} doesn't match {}\nexpected:\n{expected}\nactual:\n{actual}",
+        path.display()
+    );
+}
+
+fn check_attr(name: &str, attr: HlAttr) {
+    check_golden(name, &draw_row(1, TEXT, &regions(attr), None, false));
+}
+
+#[test]
+fn plain_text() {
+    check_attr("plain", default_attr());
+}
+
+#[test]
+fn colors_and_fonts() {
+    check_attr(
+        "bold",
+        HlAttr {
+            font: FontStyle::Bold,
+            foreground: Color::from_rgb(0.0, 0.5, 1.0),
+            ..default_attr()
+        },
+    );
+    check_attr(
+        "italic",
+        HlAttr {
+            font: FontStyle::Italic,
+            background: Color::from_rgba(0.2, 0.4, 0.2, 0.5),
+            ..default_attr()
+        },
+    );
+}
+
+#[test]
+fn decorations() {
+    check_attr(
+        "undercurl",
+        HlAttr {
+            undercurl: true,
+            ..default_attr()
+        },
+    );
+    check_attr(
+        "underline",
+        HlAttr {
+            underline: true,
+            ..default_attr()
+        },
+    );
+    check_attr(
+        "underdouble",
+        HlAttr {
+            underdouble: true,
+            ..default_attr()
+        },
+    );
+    check_attr(
+        "underdotted",
+        HlAttr {
+            underdotted: true,
+            ..default_attr()
+        },
+    );
+    check_attr(
+        "underdashed",
+        HlAttr {
+            underdashed: true,
+            ..default_attr()
+        },
+    );
+    check_attr(
+        "strikethrough",
+        HlAttr {
+            strikethrough: true,
+            ..default_attr()
+        },
+    );
+    check_attr(
+        "url",
+        HlAttr {
+            url: true,
+            ..default_attr()
+        },
+    );
+}
+
+fn parse(attrs: &[(&str, Value)]) -> HlAttr {
+    let attrs: Vec<_> = attrs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect();
+    let metrics = FontMetrics {
+        font_size: 16,
+        char_size: Vector2::new(10.0, 20.0),
+        ascent: 15.0,
+        descent: 4.0,
+    };
+    HlAttr::new(&attrs, (0xe6e6e6, 0x1a1a1a, 0xff0000), metrics)
+}
+
+#[test]
+fn parsing_hl_attr_define() {
+    // a link of `:h vim.lsp.util.open_floating_preview`, with no other flag
+    let attr = parse(&[
+        ("url", "https://neovim.io".into()),
+        ("foreground", 0x0080ff.into()),
+    ]);
+    assert!(attr.url);
+    assert!(!attr.strikethrough);
+    assert!(!attr.underline);
+    assert_eq!(attr.font, FontStyle::Normal);
+    assert_eq!(attr.foreground, Color::from_rgba8(0x00, 0x80, 0xff, 0xff));
+    assert_eq!(attr.background, Color::from_rgba8(0x1a, 0x1a, 0x1a, 0xff));
+
+    let attr = parse(&[
+        ("strikethrough", true.into()),
+        ("bold", true.into()),
+        ("reverse", true.into()),
+    ]);
+    assert!(attr.strikethrough);
+    assert!(!attr.url);
+    assert_eq!(attr.font, FontStyle::Bold);
+    assert_eq!(attr.foreground, Color::from_rgba8(0x1a, 0x1a, 0x1a, 0xff));
+    assert_eq!(attr.background, Color::from_rgba8(0xe6, 0xe6, 0xe6, 0xff));
+
+    let attr = parse(&[("blend", 25.into())]);
+    assert_eq!(attr.background.a, 0.75);
+}
+
+#[test]
+fn cursor_shapes() {
+    for (name, shape) in [
+        ("cursor_block", CursorShape::Block),
+        ("cursor_vertical", CursorShape::Vertical),
+        ("cursor_horizontal", CursorShape::Horizontal),
+    ] {
+        // on the first column of the second region, drawn once on top of both
+        let cursor = Cursor {
+            col: 3,
+            shape,
+            attr: default_attr(),
+        };
+        let commands = draw_row(1, TEXT, &regions(default_attr()), Some(&cursor), false);
+        check_golden(name, &commands);
+    }
+}
+
+#[test]
+fn ignoring_highlights() {
+    let attr = HlAttr {
+        underline: true,
+        ..default_attr()
+    };
+    check_golden("ignore_hl", &draw_row(1, TEXT, &regions(attr), None, true));
+}
+
+#[test]
+fn wide_characters() {
+    // graphemes are sliced by column, not by byte
+    let text = "日本 ok\n";
+    let commands = draw_row(0, text, &regions(default_attr()), None, false);
+    let DrawCommand::Text { text, .. } = &commands[2] else {
+        panic!("expected the text of the first region, got {}", commands[2]);
+    };
+    assert_eq!(text, "日本 ");
+}